@@ -0,0 +1,54 @@
+use core::str;
+use std::sync::Arc;
+use std::clone::Clone;
+use std::fmt::{Debug};
+use anyhow::{Context, Result};
+use arrow::array::{ArrayRef, RecordBatch};
+use clap::Args;
+use tracing::debug;
+use serde_json::json;
+use crate::evm::abi::abi_value::{abi_decode, parse_type};
+use crate::evm::abi::json_encoding::encode_value;
+use crate::cli::utils::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct EVMAbiDecodeCommand {}
+
+impl ScalarUdf for EVMAbiDecodeCommand {
+    type State = ();
+
+    fn input_columns(&self) -> &[&str] {
+        &["data", "type"]
+    }
+
+    fn init_state(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn process_batch(&self, _: &(), input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let data_col = input_batch.get_binary_column("data")?;
+        let type_col = input_batch.get_binary_column("type")?;
+
+        par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            for i in rows {
+                let res = str::from_utf8(type_col.value(i))
+                    .context("invalid UTF-8")
+                    .and_then(parse_type)
+                    .and_then(|ty| abi_decode(&ty, data_col.value(i)))
+                    .and_then(|value| encode_value(&value));
+
+                match res {
+                    Ok(js) => result_col_builder.append_value(js),
+                    Err(err) => {
+                        debug!(row = i, error = %err, "cannot decode value");
+                        result_col_builder.append_value(json!({"error": err.to_string()}).to_string())
+                    }
+                }
+            }
+
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })
+    }
+}
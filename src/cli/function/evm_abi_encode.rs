@@ -0,0 +1,64 @@
+use core::str;
+use std::sync::Arc;
+use std::clone::Clone;
+use std::fmt::{Debug};
+use anyhow::{Context, Result};
+use arrow::array::{ArrayRef, RecordBatch};
+use alloy::hex::ToHexExt;
+use clap::Args;
+use tracing::debug;
+use serde_json::{json, Value};
+use crate::evm::abi::abi_value::{abi_encode, parse_type};
+use crate::cli::utils::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct EVMAbiEncodeCommand {
+    // Encode as `abi.encodePacked`, i.e. without padding nor offsets.
+    #[arg(long)]
+    packed: bool
+}
+
+impl ScalarUdf for EVMAbiEncodeCommand {
+    type State = ();
+
+    fn input_columns(&self) -> &[&str] {
+        &["type", "values"]
+    }
+
+    fn init_state(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn process_batch(&self, _: &(), input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let type_col = input_batch.get_binary_column("type")?;
+        let values_col = input_batch.get_binary_column("values")?;
+
+        par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            for i in rows {
+                let res = str::from_utf8(type_col.value(i))
+                    .context("invalid UTF-8")
+                    .and_then(parse_type)
+                    .and_then(|ty| abi_encode(&ty, &parse_values(values_col.value(i))?, self.packed))
+                    .map(|data| json!({"value": data.encode_hex_with_prefix()}).to_string());
+
+                match res {
+                    Ok(js) => result_col_builder.append_value(js),
+                    Err(err) => {
+                        debug!(row = i, error = %err, "cannot encode values");
+                        result_col_builder.append_value(json!({"error": err.to_string()}).to_string())
+                    }
+                }
+            }
+
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })
+    }
+}
+
+// Values are JSON, a value which is not, e.g. an address or an hex string, being taken as a string.
+fn parse_values(s: &[u8]) -> Result<Value> {
+    let s = str::from_utf8(s).context("invalid UTF-8")?;
+    Ok(serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.to_string())))
+}
@@ -2,91 +2,83 @@ use std::str;
 use std::sync::Arc;
 use std::clone::Clone;
 use std::fmt::{Debug};
-use futures::stream::{iter,StreamExt};
 use anyhow::Result;
-use arrow::array::{BinaryArray, GenericByteBuilder, ListArray, RecordBatch};
-use arrow::datatypes::{DataType, Field, Schema, BinaryType};
-use arrow_ipc::reader::StreamReader;
-use arrow_ipc::writer::StreamWriter;
+use arrow::array::{BinaryArray, ArrayRef, RecordBatch};
 use clap::Args;
-use quick_cache::sync::Cache;
-use crate::evm::abi::json_encoding::*;
+use tracing::debug;
+use crate::evm::abi::AbiItemProvider;
+use crate::evm::abi::decoding::decode_call_recursive;
 use crate::cli::utils::*;
 
 #[derive(Debug, Clone, Args)]
 pub struct EVMDecodeCallCommand {
-    #[arg(short, long, default_value = "")]
-    input_file: String,
+    #[command(flatten)]
+    abi_cache: AbiCacheArgs,
 
-    #[arg(short, long, default_value = "")]
-    output_file: String,
-
-    #[arg(short, long, default_value_t = 2000)]
-    abi_provider_cache_size: usize
+    // Maximum depth of the calls held in `bytes` values, e.g. the calls of a multicall, decoded in place with
+    // the decoders of the row, 0 to leave them encoded.
+    #[arg(long, default_value_t = 0)]
+    recursive_depth: usize
 }
 
-impl EVMDecodeCallCommand {
-    pub async fn run(&self) -> Result<()> {
-        let cache = Arc::new(Cache::new(self.abi_provider_cache_size));
-        let mut input_file = open_file_or_stdin(&self.input_file)?;
-        let mut output_file = create_file_or_stdout(&self.output_file)?;
-        let output_schema = Arc::new(Schema::new(vec![
-            Field::new("result", DataType::Binary, false),
-        ]));
+impl ScalarUdf for EVMDecodeCallCommand {
+    type State = AbiItemProviderCache;
 
-        loop {
-            let reader = StreamReader::try_new_buffered(&mut input_file, None)?;
-            let mut writer = StreamWriter::try_new_buffered(&mut output_file, &output_schema)?;
+    fn input_columns(&self) -> &[&str] {
+        &["input", "output", "abis"]
+    }
 
-            for input_batch in reader {
-                let input_batch = input_batch?;
+    fn init_state(&self) -> Result<AbiItemProviderCache> {
+        self.abi_cache.build()
+    }
 
-                let mut result_col_builder = GenericByteBuilder::<BinaryType>::with_capacity(
-                    input_batch.num_rows(),
-                    input_batch.num_rows() * 1024
-                );
+    async fn process_batch(&self, cache: &AbiItemProviderCache, input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let input_col = input_batch.get_binary_column("input")?; 
+        let output_col = input_batch.get_binary_column("output")?; 
+        let abis_col = input_batch.get_binary_list_column("abis")?;
+        let ctx_cols = AbiContextColumns::new(input_batch)?;
 
-                let input_col: &BinaryArray = input_batch.get_column("input")?; 
-                let output_col: &BinaryArray = input_batch.get_column("output")?; 
-                let abis_col: &ListArray = input_batch.get_column("abis")?;
+        let providers = prefetch_abi_item_providers(cache, &abis_col, &ctx_cols).await;
 
-                for i in 0..input_batch.num_rows() {
-                    let input = input_col.value(i);
-                    let output = output_col.value(i);
-                    let abis = abis_col.value(i);
-                    let abis: &BinaryArray  = abis.as_array()?;
+        let result_col = par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
 
-                    let res= iter(abis)
-                        .map(|key| {
-                            let cache = cache.clone();
+            for i in rows {
+                let ctx = ctx_cols.get(i);
+                let input = input_col.value(i);
+                let output = output_col.value(i);
+                let abis = abis_col.value(i);
+                let abis: &BinaryArray  = abis.as_array()?;
 
-                            async move {
-                                let p = get_cached_abi_item_provider(cache, key.unwrap()).await?;
-                                let func = p.get_function(&input[0..4])?;
+                let row_providers: Vec<&dyn AbiItemProvider> = abis
+                    .iter()
+                    .flatten()
+                    .filter_map(|key| providers.get(key, &ctx))
+                    .map(|p| p.as_ref() as &dyn AbiItemProvider)
+                    .collect();
 
-                                encode_call(
-                                    func,
-                                    func.abi_decode_input(&input[4..])?.iter(), 
-                                    Some(func.abi_decode_output(output)?.iter())
-                                )
-                            }
-                        })
-                        .filter_map(|f| Box::pin(async { f.into_future().await.ok() }))
-                        .next()
-                        .await;
+                let res = row_providers
+                    .iter()
+                    .find_map(|p| {
+                        decode_call_recursive(*p, &row_providers, input, Some(output), self.recursive_depth)
+                            .inspect_err(|err| debug!(row = i, error = %err, "cannot decode call with decoder"))
+                            .ok()
+                    });
 
-                    match res {
-                        Some(js) => result_col_builder.append_value(js),
-                        None => result_col_builder.append_value(b"{\"error\": \"cannot decode call\"}"),
-                    }
+                match res {
+                    Some(js) => result_col_builder.append_value(js),
+                    None => result_col_builder.append_value(b"{\"error\": \"cannot decode call\"}"),
                 }
+            }
 
-                let result_col = result_col_builder.finish();
-                let output_batch = RecordBatch::try_new(output_schema.clone(), vec![Arc::new(result_col)])?;
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })?;
 
-                writer.write(&output_batch)?;
-                writer.flush()?;
-            }
-        }
+        debug!(
+            cache_hit_ratio = cache.hit_ratio(),
+            "ABI item provider cache"
+        );
+
+        Ok(result_col)
     }
 }
\ No newline at end of file
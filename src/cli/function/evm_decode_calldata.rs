@@ -2,89 +2,118 @@ use std::str;
 use std::sync::Arc;
 use std::clone::Clone;
 use std::fmt::{Debug};
-use futures::stream::{iter,StreamExt};
 use anyhow::Result;
-use arrow::array::{BinaryArray, GenericByteBuilder, ListArray, RecordBatch};
-use arrow::datatypes::{DataType, Field, Schema, BinaryType};
-use arrow_ipc::reader::StreamReader;
-use arrow_ipc::writer::StreamWriter;
+use arrow::array::{BinaryArray, ArrayRef, RecordBatch};
 use clap::Args;
-use quick_cache::sync::Cache;
-use crate::evm::abi::json_encoding::*;
+use tracing::debug;
+use crate::evm::abi::{AbiContext, AbiItemProvider};
+use crate::evm::abi::decoding::decode_call_recursive;
 use crate::cli::utils::*;
 
 #[derive(Debug, Clone, Args)]
 pub struct EVMDecodeCalldataCommand {
-    #[arg(short, long, default_value = "")]
-    input_file: String,
+    #[command(flatten)]
+    abi_cache: AbiCacheArgs,
 
-    #[arg(short, long, default_value = "")]
-    output_file: String,
+    // Maximum depth of the calls held in `bytes` values, e.g. the calls of a multicall, decoded in place with
+    // the decoders of the row, 0 to leave them encoded.
+    #[arg(long, default_value_t = 0)]
+    recursive_depth: usize
+}
+
+impl ScalarUdf for EVMDecodeCalldataCommand {
+    type State = AbiItemProviderCache;
+
+    fn input_columns(&self) -> &[&str] {
+        &["input", "abis"]
+    }
+
+    fn init_state(&self) -> Result<AbiItemProviderCache> {
+        self.abi_cache.build()
+    }
+
+    async fn process_batch(&self, cache: &AbiItemProviderCache, input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let input_col = input_batch.get_binary_column("input")?; 
+        let abis_col = input_batch.get_binary_list_column("abis")?;
+
+        let providers = prefetch_abi_item_providers(cache, &abis_col, &AbiContextColumns::default()).await;
+
+        let result_col = par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            for i in rows {
+                let input = input_col.value(i);
+                let abis = abis_col.value(i);
+                let abis: &BinaryArray  = abis.as_array()?;
+
+                let row_providers: Vec<&dyn AbiItemProvider> = abis
+                    .iter()
+                    .flatten()
+                    .filter_map(|key| providers.get(key, &AbiContext::default()))
+                    .map(|p| p.as_ref() as &dyn AbiItemProvider)
+                    .collect();
 
-    #[arg(short, long, default_value_t = 2000)]
-    abi_provider_cache_size: usize
+                let res = row_providers
+                    .iter()
+                    .find_map(|p| {
+                        decode_call_recursive(*p, &row_providers, input, None, self.recursive_depth)
+                            .inspect_err(|err| debug!(row = i, error = %err, "cannot decode call with decoder"))
+                            .ok()
+                    });
+
+                match res {
+                    Some(js) => result_col_builder.append_value(js),
+                    None => result_col_builder.append_value(b"{\"error\": \"cannot decode call\"}"),
+                }
+            }
+
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })?;
+
+        debug!(
+            cache_hit_ratio = cache.hit_ratio(),
+            "ABI item provider cache"
+        );
+
+        Ok(result_col)
+    }
 }
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::U256;
+    use arrow::array::{Array, AsArray};
+    use serde_json::Value;
+    use super::*;
 
-impl EVMDecodeCalldataCommand {
-    pub async fn run(&self) -> Result<()> {
-        let cache = Arc::new(Cache::new(self.abi_provider_cache_size));
-        let mut input_file = open_file_or_stdin(&self.input_file)?;
-        let mut output_file = create_file_or_stdout(&self.output_file)?;
-        let output_schema = Arc::new(Schema::new(vec![
-            Field::new("result", DataType::Binary, false),
-        ]));
-
-        loop {
-            let reader = StreamReader::try_new_buffered(&mut input_file, None)?;
-            let mut writer = StreamWriter::try_new_buffered(&mut output_file, &output_schema)?;
-
-            for input_batch in reader {
-                let input_batch = input_batch?;
-
-                let mut result_col_builder = GenericByteBuilder::<BinaryType>::with_capacity(
-                    input_batch.num_rows(),
-                    input_batch.num_rows() * 1024
-                );
-
-                let input_col: &BinaryArray = input_batch.get_column("input")?; 
-                let abis_col: &ListArray = input_batch.get_column("abis")?;
-
-                for i in 0..input_batch.num_rows() {
-                    let input = input_col.value(i);
-                    let abis = abis_col.value(i);
-                    let abis: &BinaryArray  = abis.as_array()?;
-
-                    let res= iter(abis)
-                        .map(|key| {
-                            let cache = cache.clone();
-
-                            async move {
-                                let p = get_cached_abi_item_provider(cache, key.unwrap()).await?;
-                                let func = p.get_function(&input[0..4])?;
-
-                                encode_call(
-                                    func,
-                                    func.abi_decode_input(&input[4..])?.iter(),
-                                    None
-                                )
-                            }
-                        })
-                        .filter_map(|f| Box::pin(async { f.into_future().await.ok() }))
-                        .next()
-                        .await;
-
-                        match res {
-                            Some(js) => result_col_builder.append_value(js),
-                            None => result_col_builder.append_value(b"{\"error\": \"cannot decode call\"}"),
-                        }
-                    }
-
-                let result_col = result_col_builder.finish();
-                let output_batch = RecordBatch::try_new(output_schema.clone(), vec![Arc::new(result_col)])?;
-
-                writer.write(&output_batch)?;
-                writer.flush()?;
+    // `transfer(0x1111111111111111111111111111111111111111, amount)`
+    fn transfer(amount: usize) -> Vec<u8> {
+        [&[0xa9, 0x05, 0x9c, 0xbb], [0; 12].as_slice(), &[0x11; 20], &U256::from(amount).to_be_bytes::<32>()].concat()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn decodes_rows_in_order() {
+        let num_rows = 1000;
+        let inputs: Vec<Vec<u8>> = (0..num_rows).map(|i| match i % 100 {
+            99 => b"\x12\x34".to_vec(),
+            _ => transfer(i)
+        }).collect();
+        let abi = b"function transfer(address to, uint256 amount) returns (bool)".as_slice();
+        let batch = RecordBatch::try_from_iter([
+            ("input", Arc::new(BinaryArray::from_iter_values(&inputs)) as ArrayRef),
+            ("abis", Arc::new(binary_list_array(vec![Some(vec![Some(abi)]); num_rows])) as ArrayRef)
+        ]).unwrap();
+
+        let cmd: EVMDecodeCalldataCommand = default_args();
+        let res = cmd.process_batch(&cmd.init_state().unwrap(), &batch).await.unwrap();
+        let res = res.as_binary::<i32>();
+        assert_eq!(res.len(), num_rows);
+
+        for (i, js) in res.iter().enumerate() {
+            let js: Value = serde_json::from_slice(js.unwrap()).unwrap();
+            match i % 100 {
+                99 => assert_eq!(js["error"], "cannot decode call", "row {i}"),
+                _ => assert_eq!(js["value"]["inputs"]["arg1"], i, "row {i}")
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,83 @@
+use std::str;
+use std::sync::Arc;
+use std::clone::Clone;
+use std::fmt::{Debug};
+use anyhow::Result;
+use arrow::array::{Array, BinaryArray, ArrayRef, RecordBatch};
+use clap::Args;
+use tracing::debug;
+use crate::evm::abi::AbiContext;
+use crate::evm::abi::creation_code::CreationCode;
+use crate::evm::abi::decoding::decode_constructor;
+use crate::cli::utils::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct EVMDecodeConstructorCommand {
+    #[command(flatten)]
+    abi_cache: AbiCacheArgs
+}
+
+impl ScalarUdf for EVMDecodeConstructorCommand {
+    type State = AbiItemProviderCache;
+
+    fn input_columns(&self) -> &[&str] {
+        &["input", "abis"]
+    }
+
+    fn init_state(&self) -> Result<AbiItemProviderCache> {
+        self.abi_cache.build()
+    }
+
+    async fn process_batch(&self, cache: &AbiItemProviderCache, input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let input_col = input_batch.get_binary_column("input")?;
+        let abis_col = input_batch.get_binary_list_column("abis")?;
+        // Optional columns locating the constructor arguments, see the `_with_*` variants of the function.
+        let creation_code_col = input_batch.get_optional_binary_column("creation_code")?;
+        let runtime_length_col = input_batch.get_optional_int64_column("runtime_length")?;
+
+        let providers = prefetch_abi_item_providers(cache, &abis_col, &AbiContextColumns::default()).await;
+
+        let result_col = par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            for i in rows {
+                let input = input_col.value(i);
+                let abis = abis_col.value(i);
+                let abis: &BinaryArray  = abis.as_array()?;
+
+                let creation_code = creation_code_col.as_ref()
+                    .filter(|col| col.is_valid(i))
+                    .map(|col| CreationCode::Known(col.value(i)));
+                let runtime_length = runtime_length_col.as_ref()
+                    .filter(|col| col.is_valid(i))
+                    .and_then(|col| usize::try_from(col.value(i)).ok())
+                    .map(CreationCode::RuntimeLength);
+                let code = creation_code.or(runtime_length).unwrap_or(CreationCode::Unknown);
+
+                let res = abis
+                    .iter()
+                    .flatten()
+                    .filter_map(|key| providers.get(key, &AbiContext::default()))
+                    .find_map(|p| {
+                        decode_constructor(p.as_ref(), input, code)
+                            .inspect_err(|err| debug!(row = i, error = %err, "cannot decode constructor with decoder"))
+                            .ok()
+                    });
+
+                match res {
+                    Some(js) => result_col_builder.append_value(js),
+                    None => result_col_builder.append_value(b"{\"error\": \"cannot decode constructor\"}"),
+                }
+            }
+
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })?;
+
+        debug!(
+            cache_hit_ratio = cache.hit_ratio(),
+            "ABI item provider cache"
+        );
+
+        Ok(result_col)
+    }
+}
@@ -2,95 +2,129 @@ use std::str;
 use std::sync::Arc;
 use std::clone::Clone;
 use std::fmt::{Debug};
-use futures::stream::{iter,StreamExt};
-use anyhow::Result;
-use arrow::array::{BinaryArray, FixedSizeBinaryArray, GenericByteBuilder, ListArray, RecordBatch};
-use arrow::datatypes::{DataType, Field, Schema, BinaryType};
-use arrow_ipc::reader::StreamReader;
-use arrow_ipc::writer::StreamWriter;
-use alloy::primitives::FixedBytes;
+use anyhow::{anyhow, Result};
+use arrow::array::{BinaryArray, ArrayRef, RecordBatch};
+use alloy::primitives::B256;
 use clap::Args;
-use quick_cache::sync::Cache;
-use crate::evm::abi::json_encoding::*;
+use serde_json::json;
+use tracing::debug;
+use crate::evm::abi::decoding::decode_event;
 use crate::cli::utils::*;
 
 #[derive(Debug, Clone, Args)]
 pub struct EVMDecodeEventCommand {
-    #[arg(short, long, default_value = "")]
-    input_file: String,
+    #[command(flatten)]
+    abi_cache: AbiCacheArgs
+}
 
-    #[arg(short, long, default_value = "")]
-    output_file: String,
+impl ScalarUdf for EVMDecodeEventCommand {
+    type State = AbiItemProviderCache;
 
-    #[arg(short, long, default_value_t = 2000)]
-    abi_provider_cache_size: usize
-}
+    fn input_columns(&self) -> &[&str] {
+        &["topics", "data", "abis"]
+    }
 
-impl EVMDecodeEventCommand {
-    pub async fn run(&self) -> Result<()> {
-        let cache = Arc::new(Cache::new(self.abi_provider_cache_size));
-        let mut input_file = open_file_or_stdin(&self.input_file)?;
-        let mut output_file = create_file_or_stdout(&self.output_file)?;
-        let output_schema = Arc::new(Schema::new(vec![
-            Field::new("result", DataType::Binary, false),
-        ]));
-
-        loop {
-            let reader = StreamReader::try_new_buffered(&mut input_file, None)?;
-            let mut writer = StreamWriter::try_new_buffered(&mut output_file, &output_schema)?;
-
-            for input_batch in reader {
-                let input_batch = input_batch?;
-
-                let mut result_col_builder = GenericByteBuilder::<BinaryType>::with_capacity(
-                    input_batch.num_rows(),
-                    input_batch.num_rows() * 1024
-                );
-
-                let topics_col: &ListArray = input_batch.get_column("topics")?;
-                let data_col: &BinaryArray = input_batch.get_column("data")?; 
-                let abis_col: &ListArray = input_batch.get_column("abis")?;
-
-                for i in 0..input_batch.num_rows() {
-                    let topics = topics_col.value(i);
-                    let topics: &FixedSizeBinaryArray = topics.as_array()?;
-
-                    let data = data_col.value(i);
-
-                    let abis = abis_col.value(i);
-                    let abis: &BinaryArray  = abis.as_array()?;
-
-                    let res= iter(abis)
-                        .map(|key| {
-                            let cache = cache.clone();
-
-                            async move {
-                                let p = get_cached_abi_item_provider(cache, key.unwrap()).await?;
-                                let evt = p.get_event(topics.value(0))?;
-                                let topics = topics
-                                    .iter()
-                                    .flatten()
-                                    .map(|x| FixedBytes::from_slice(x));
-                                let decoded_evt = evt.decode_log_parts(topics, data)?;
-                                encode_event(evt, &decoded_evt)
-                            }
-                        })
-                        .filter_map(|f| Box::pin(async { f.into_future().await.ok() }))
-                        .next()
-                        .await;
-
-                        match res {
-                            Some(js) => result_col_builder.append_value(js),
-                            None => result_col_builder.append_value(b"{\"error\": \"cannot decode event\"}"),
-                        }
+    fn init_state(&self) -> Result<AbiItemProviderCache> {
+        self.abi_cache.build()
+    }
+
+    async fn process_batch(&self, cache: &AbiItemProviderCache, input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let topics_col = input_batch.get_binary_list_column("topics")?;
+        let data_col = input_batch.get_binary_column("data")?; 
+        let abis_col = input_batch.get_binary_list_column("abis")?;
+        let ctx_cols = AbiContextColumns::new(input_batch)?;
+
+        let providers = prefetch_abi_item_providers(cache, &abis_col, &ctx_cols).await;
+
+        let result_col = par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            for i in rows {
+                let ctx = ctx_cols.get(i);
+                let topics = topics_col.value(i);
+                let topics: &BinaryArray = topics.as_array()?;
+                // NULL topics are not skipped: the position of a topic tells which indexed parameter it is.
+                let topics = topics
+                    .iter()
+                    .map(|topic| match topic {
+                        Some(topic) => B256::try_from(topic).map_err(|_| anyhow!("topics must be 32 bytes long")),
+                        None => Err(anyhow!("topics must not be NULL"))
+                    })
+                    .collect::<Result<Vec<_>>>();
+
+                let topics = match topics {
+                    Ok(topics) => topics,
+                    Err(err) => {
+                        result_col_builder.append_value(json!({"error": err.to_string()}).to_string());
+                        continue;
                     }
+                };
+
+                let data = data_col.value(i);
+
+                let abis = abis_col.value(i);
+                let abis: &BinaryArray  = abis.as_array()?;
+
+                let res = abis
+                    .iter()
+                    .flatten()
+                    .filter_map(|key| providers.get(key, &ctx))
+                    .find_map(|p| {
+                        decode_event(p.as_ref(), &topics, data)
+                            .inspect_err(|err| debug!(row = i, error = %err, "cannot decode event with decoder"))
+                            .ok()
+                    });
+
+                match res {
+                    Some(js) => result_col_builder.append_value(js),
+                    None => result_col_builder.append_value(b"{\"error\": \"cannot decode event\"}"),
+                }
+            }
 
-                let result_col = result_col_builder.finish();
-                let output_batch = RecordBatch::try_new(output_schema.clone(), vec![Arc::new(result_col)])?;
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })?;
 
-                writer.write(&output_batch)?;
-                writer.flush()?;
-            }
-        }
+        debug!(
+            cache_hit_ratio = cache.hit_ratio(),
+            "ABI item provider cache"
+        );
+
+        Ok(result_col)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+    use arrow::array::AsArray;
+    use super::*;
+
+    const TRANSFER: [u8; 32] = hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+    const FROM: [u8; 32] = hex!("000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+    const VALUE: [u8; 32] = hex!("0000000000000000000000000000000000000000000000000de0b6b3a7640000");
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn returns_errors_for_null_or_invalid_topics() {
+        let abi = b"event Transfer(address indexed from, address indexed to, uint256 value)".as_slice();
+        let batch = RecordBatch::try_from_iter([
+            ("topics", Arc::new(binary_list_array(vec![
+                Some(vec![Some(&TRANSFER), Some(&FROM), Some(&FROM)]),
+                Some(vec![Some(&TRANSFER), None, Some(&FROM)]),
+                Some(vec![Some(&TRANSFER), Some(&FROM[1..]), Some(&FROM)])
+            ])) as ArrayRef),
+            ("data", Arc::new(BinaryArray::from_vec(vec![&VALUE, &VALUE, &VALUE])) as ArrayRef),
+            ("abis", Arc::new(binary_list_array(vec![Some(vec![Some(abi)]); 3])) as ArrayRef)
+        ]).unwrap();
+
+        let cmd: EVMDecodeEventCommand = default_args();
+        let res = cmd.process_batch(&cmd.init_state().unwrap(), &batch).await.unwrap();
+        let res: Vec<serde_json::Value> = res.as_binary::<i32>().iter()
+            .map(|v| serde_json::from_slice(v.unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(res.len(), batch.num_rows());
+        assert_eq!(res[0]["value"]["signature"], "Transfer(address,address,uint256)");
+        assert_eq!(res[0]["value"]["inputs"]["arg2"], "1000000000000000000");
+        assert_eq!(res[1], json!({"error": "topics must not be NULL"}));
+        assert_eq!(res[2], json!({"error": "topics must be 32 bytes long"}));
+    }
+}
@@ -0,0 +1,96 @@
+use std::str;
+use std::sync::Arc;
+use std::clone::Clone;
+use std::fmt::{Debug};
+use anyhow::{anyhow, Result};
+use arrow::array::{BinaryArray, ArrayRef, RecordBatch};
+use clap::Args;
+use serde_json::json;
+use tracing::debug;
+use crate::evm::abi::{AbiContext, AbiItemProvider};
+use crate::evm::abi::trace_decoding::{decode_trace, parse_trace, trace_addresses};
+use crate::cli::utils::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct EVMDecodeTraceCommand {
+    #[command(flatten)]
+    abi_cache: AbiCacheArgs,
+
+    // Return the frames as an array, each one with its trace address, instead of a tree.
+    #[arg(long)]
+    flatten: bool
+}
+
+impl ScalarUdf for EVMDecodeTraceCommand {
+    type State = AbiItemProviderCache;
+
+    fn input_columns(&self) -> &[&str] {
+        &["trace", "abis"]
+    }
+
+    fn init_state(&self) -> Result<AbiItemProviderCache> {
+        self.abi_cache.build()
+    }
+
+    async fn process_batch(&self, cache: &AbiItemProviderCache, input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let trace_col = input_batch.get_binary_column("trace")?;
+        let abis_col = input_batch.get_binary_list_column("abis")?;
+        let ctx_cols = AbiContextColumns::new(input_batch)?;
+
+        let traces: Vec<_> = trace_col.iter()
+            .map(|trace| parse_trace(trace.unwrap_or_default()))
+            .collect();
+
+        // The frames and logs of a trace are decoded with the ABI of their own contract, for the decoders
+        // depending on the address.
+        let providers = prefetch_abi_item_providers_with(cache, &abis_col, |i| {
+            let ctx = ctx_cols.get(i);
+            let addresses = traces[i].as_ref().map(trace_addresses).unwrap_or_default();
+
+            addresses.into_iter()
+                .map(|address| AbiContext { address: Some(address), ..ctx.clone() })
+                .chain([ctx.clone()])
+                .collect()
+        }).await;
+
+        let result_col = par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            for i in rows {
+                let ctx = ctx_cols.get(i);
+                let abis = abis_col.value(i);
+                let abis: &BinaryArray  = abis.as_array()?;
+
+                let frame_providers = |address| {
+                    let ctx = AbiContext { address, ..ctx.clone() };
+                    abis.iter()
+                        .flatten()
+                        .filter_map(|key| providers.get(key, &ctx))
+                        .map(|p| p.as_ref() as &dyn AbiItemProvider)
+                        .collect()
+                };
+
+                let res = traces[i].as_ref()
+                    .map_err(|err| anyhow!("{err}"))
+                    .and_then(|trace| decode_trace(trace, frame_providers, self.flatten));
+
+                match res {
+                    Ok(js) => result_col_builder.append_value(js),
+                    Err(err) => {
+                        debug!(row = i, error = %err, "cannot decode trace");
+                        result_col_builder.append_value(json!({"error": err.to_string()}).to_string())
+                    }
+                }
+            }
+
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })?;
+
+        debug!(
+            cache_hit_ratio = cache.hit_ratio(),
+            "ABI item provider cache"
+        );
+
+        Ok(result_col)
+    }
+}
@@ -0,0 +1,101 @@
+use core::str;
+use std::sync::Arc;
+use std::clone::Clone;
+use std::fmt::{Debug};
+use anyhow::{bail, Context, Result};
+use arrow::array::{ArrayRef, RecordBatch};
+use clap::Args;
+use quick_cache::sync::Cache;
+use tracing::debug;
+use serde_json::Value;
+use crate::evm::abi::Function;
+use crate::cli::utils::*;
+
+#[derive(Debug, Clone, Args)]
+pub struct EVMEncodeCalldataCommand {}
+
+impl ScalarUdf for EVMEncodeCalldataCommand {
+    type State = Arc<Cache<String, Function>>;
+
+    fn input_columns(&self) -> &[&str] {
+        &["fullsig", "args"]
+    }
+
+    fn init_state(&self) -> Result<Arc<Cache<String, Function>>> {
+        Ok(Arc::new(Cache::new(100)))
+    }
+
+    async fn process_batch(&self, cache: &Arc<Cache<String, Function>>, input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let fullsig_col = input_batch.get_binary_column("fullsig")?;
+        let args_col = input_batch.get_binary_column("args")?;
+
+        par_process_chunks(input_batch.num_rows(), |rows| {
+            let mut result_col_builder = binary_result_builder(rows.len());
+
+            // The calldata is returned as raw bytes, the rows which cannot be encoded get NULL.
+            for i in rows {
+                let res = get_cached_func_sync(cache.clone(), fullsig_col.value(i))
+                    .and_then(|func| encode_calldata(&func, args_col.value(i)));
+
+                match res {
+                    Ok(data) => result_col_builder.append_value(data),
+                    Err(err) => {
+                        debug!(row = i, error = %err, "cannot encode calldata");
+                        result_col_builder.append_null()
+                    }
+                }
+            }
+
+            Ok(Arc::new(result_col_builder.finish()) as ArrayRef)
+        })
+    }
+}
+
+fn encode_calldata(func: &Function, args: &[u8]) -> Result<Vec<u8>> {
+    let args: Value = serde_json::from_slice(args).context("cannot parse arguments")?;
+    let Value::Array(args) = args else {
+        bail!("arguments must be a JSON array");
+    };
+
+    Ok(func.abi_encode_input(&func.coerce_inputs(&args)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, AsArray, BinaryArray};
+    use alloy::hex;
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn encodes_calldata_as_bytes() {
+        let transfer = b"function transfer(address to, uint256 amount)".as_slice();
+        let batch = RecordBatch::try_from_iter([
+            ("fullsig", Arc::new(BinaryArray::from_vec(vec![transfer, transfer, transfer, b"function transfer("])) as ArrayRef),
+            ("args", Arc::new(BinaryArray::from_vec(vec![
+                br#"["0x1111111111111111111111111111111111111111", "1000000000000000000000"]"#.as_slice(),
+                br#"["0x1111111111111111111111111111111111111111"]"#,
+                br#"{"to": "0x1111111111111111111111111111111111111111"}"#,
+                b"[]"
+            ])) as ArrayRef)
+        ]).unwrap();
+
+        let cmd = EVMEncodeCalldataCommand {};
+        let res = cmd.process_batch(&cmd.init_state().unwrap(), &batch).await.unwrap();
+        let res = res.as_binary::<i32>();
+
+        assert_eq!(res.len(), batch.num_rows());
+        assert_eq!(res.value(0), hex!("a9059cbb000000000000000000000000111111111111111111111111111111111111111100000000000000000000000000000000000000000000003635c9adc5dea00000"));
+        assert!((1..4).all(|i| res.is_null(i)));
+    }
+
+    #[test]
+    fn reports_mismatching_arguments() {
+        let func = Function::parse("function transfer(address to, uint256 amount)").unwrap();
+        let err = |args: &[u8]| encode_calldata(&func, args).unwrap_err().to_string();
+
+        assert_eq!(err(br#"["0x1111111111111111111111111111111111111111"]"#), "transfer(address,uint256) expects 2 arguments, got 1");
+        assert_eq!(err(br#"{}"#), "arguments must be a JSON array");
+        assert_eq!(err(b"[0x11"), "cannot parse arguments");
+        assert!(err(br#"["0x11", "1"]"#).starts_with("invalid argument 0 (address)"));
+    }
+}
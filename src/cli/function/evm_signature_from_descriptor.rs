@@ -3,81 +3,63 @@ use std::sync::Arc;
 use std::clone::Clone;
 use std::fmt::{Debug};
 use anyhow::{bail, Context, Result};
-use arrow::array::{BinaryArray, GenericByteBuilder, RecordBatch};
-use arrow::datatypes::{DataType, Field, Schema, BinaryType};
-use arrow_ipc::reader::StreamReader;
-use arrow_ipc::writer::StreamWriter;
+use arrow::array::{ArrayRef, RecordBatch};
 use alloy::json_abi::AbiItem;
 use clap::Args;
+use tracing::debug;
 use serde_json::json;
 use crate::cli::utils::*;
 use crate::evm::abi::AbiItemExt;
 
 #[derive(Debug, Clone, Args)]
-pub struct EVMSignatureFromDescriptorCommand {
-    #[arg(short, long, default_value = "")]
-    input_file: String,
+pub struct EVMSignatureFromDescriptorCommand {}
 
-    #[arg(short, long, default_value = "")]
-    output_file: String
-}
+impl ScalarUdf for EVMSignatureFromDescriptorCommand {
+    type State = ();
 
-impl EVMSignatureFromDescriptorCommand {
-    pub async fn run(&self) -> Result<()> {
-        let mut input_file = open_file_or_stdin(&self.input_file)?;
-        let mut output_file = create_file_or_stdout(&self.output_file)?;
-        let output_schema = Arc::new(Schema::new(vec![
-            Field::new("result", DataType::Binary, false),
-        ]));
-
-        loop {
-            let reader = StreamReader::try_new_buffered(&mut input_file, None)?;
-            let mut writer = StreamWriter::try_new_buffered(&mut output_file, &output_schema)?;
-
-            for input_batch in reader {
-                let input_batch = input_batch?;
-
-                let mut result_col_builder = GenericByteBuilder::<BinaryType>::with_capacity(
-                    input_batch.num_rows(),
-                    input_batch.num_rows() * 1024
-                );
+    fn input_columns(&self) -> &[&str] {
+        &["event_descriptor"]
+    }
 
-                let event_descriptor_col: &BinaryArray = input_batch.get_column("event_descriptor")?; 
+    fn init_state(&self) -> Result<()> {
+        Ok(())
+    }
 
-                for i in 0..input_batch.num_rows() {
-                    let f = str::from_utf8(event_descriptor_col.value(i))
-                        .context("invalid UTF-8")
-                        .and_then(|s| Ok(serde_json::from_str::<serde_json::Value>(s)?))
-                        .and_then(|s| Ok(serde_json::from_value::<AbiItem>(s)?))
-                        .map(|mut i| { i.scrub_param_names(); i })
-                        .and_then(|i| {
-                            match i {
-                                AbiItem::Function(f) => Ok(json!({"value": {
-                                    "selector": f.selector().to_string(),
-                                    "signature": f.signature(),
-                                    "fullsig": f.full_signature()
-                                }}).to_string()),
-                                AbiItem::Event(e) => Ok(json!({"value": {
-                                    "selector": e.selector().to_string(),
-                                    "signature": e.signature(),
-                                    "fullsig": e.full_signature()
-                                }}).to_string()),
-                                _ => bail!("")
-                            }
-                        });
+    async fn process_batch(&self, _: &(), input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let mut result_col_builder = binary_result_builder(input_batch.num_rows());
+        let event_descriptor_col = input_batch.get_binary_column("event_descriptor")?; 
 
-                    match f {
-                        Err(err) => result_col_builder.append_value(json!({"error": err.to_string()}).to_string()),
-                        Ok(s) => result_col_builder.append_value(s),
+        for i in 0..input_batch.num_rows() {
+            let f = str::from_utf8(event_descriptor_col.value(i))
+                .context("invalid UTF-8")
+                .and_then(|s| Ok(serde_json::from_str::<serde_json::Value>(s)?))
+                .and_then(|s| Ok(serde_json::from_value::<AbiItem>(s)?))
+                .map(|mut i| { i.scrub_param_names(); i })
+                .and_then(|i| {
+                    match i {
+                        AbiItem::Function(f) => Ok(json!({"value": {
+                            "selector": f.selector().to_string(),
+                            "signature": f.signature(),
+                            "fullsig": f.full_signature()
+                        }}).to_string()),
+                        AbiItem::Event(e) => Ok(json!({"value": {
+                            "selector": e.selector().to_string(),
+                            "signature": e.signature(),
+                            "fullsig": e.full_signature()
+                        }}).to_string()),
+                        _ => bail!("")
                     }
-                }
-
-                let result_col = result_col_builder.finish();
-                let output_batch = RecordBatch::try_new(output_schema.clone(), vec![Arc::new(result_col)])?;
+                });
 
-                writer.write(&output_batch)?;
-                writer.flush()?;
+            match f {
+                Err(err) => {
+                    debug!(row = i, error = %err, "cannot process row");
+                    result_col_builder.append_value(json!({"error": err.to_string()}).to_string())
+                },
+                Ok(s) => result_col_builder.append_value(s),
             }
         }
+
+        Ok(Arc::new(result_col_builder.finish()))
     }
 }
\ No newline at end of file
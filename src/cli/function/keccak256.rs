@@ -2,55 +2,52 @@ use std::sync::Arc;
 use std::clone::Clone;
 use std::fmt::{Debug};
 use anyhow::Result;
-use arrow::array::{BinaryArray, GenericByteBuilder, RecordBatch};
-use arrow::datatypes::{DataType, Field, Schema, BinaryType};
-use arrow_ipc::reader::StreamReader;
-use arrow_ipc::writer::StreamWriter;
+use arrow::array::{ArrayRef, RecordBatch};
 use alloy::primitives::keccak256;
 use clap::Args;
 use crate::cli::utils::*;
 
 #[derive(Debug, Clone, Args)]
-pub struct Keccak256Command {
-    #[arg(short, long, default_value = "")]
-    input_file: String,
+pub struct Keccak256Command {}
 
-    #[arg(short, long, default_value = "")]
-    output_file: String
-}
-
-impl Keccak256Command {
-    pub async fn run(&self) -> Result<()> {
-        let mut input_file = open_file_or_stdin(&self.input_file)?;
-        let mut output_file = create_file_or_stdout(&self.output_file)?;
-        let output_schema = Arc::new(Schema::new(vec![
-            Field::new("result", DataType::Binary, false),
-        ]));
-
-        loop {
-            let reader = StreamReader::try_new_buffered(&mut input_file, None)?;
-            let mut writer = StreamWriter::try_new_buffered(&mut output_file, &output_schema)?;
+impl ScalarUdf for Keccak256Command {
+    type State = ();
 
-            for input_batch in reader {
-                let input_batch = input_batch?;
-
-                let mut result_col_builder = GenericByteBuilder::<BinaryType>::with_capacity(
-                    input_batch.num_rows(),
-                    input_batch.num_rows() * 1024
-                );
-
-                let str_col: &BinaryArray = input_batch.get_column("str")?; 
+    fn input_columns(&self) -> &[&str] {
+        &["str"]
+    }
 
-                for i in 0..input_batch.num_rows() {
-                    result_col_builder.append_value(keccak256(str_col.value(i)));
-                }
+    fn init_state(&self) -> Result<()> {
+        Ok(())
+    }
 
-                let result_col = result_col_builder.finish();
-                let output_batch = RecordBatch::try_new(output_schema.clone(), vec![Arc::new(result_col)])?;
+    async fn process_batch(&self, _: &(), input_batch: &RecordBatch) -> Result<ArrayRef> {
+        let mut result_col_builder = binary_result_builder(input_batch.num_rows());
+        let str_col = input_batch.get_binary_column("str")?; 
 
-                writer.write(&output_batch)?;
-                writer.flush()?;
-            }
+        for i in 0..input_batch.num_rows() {
+            result_col_builder.append_value(keccak256(str_col.value(i)));
         }
+
+        Ok(Arc::new(result_col_builder.finish()))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, AsArray, BinaryArray};
+    use alloy::hex;
+    use super::*;
+
+    #[tokio::test]
+    async fn hashes_rows() {
+        let batch = RecordBatch::try_from_iter([
+            ("str", Arc::new(BinaryArray::from_vec(vec![b"", b"Transfer(address,address,uint256)"])) as ArrayRef)
+        ]).unwrap();
+        let res = Keccak256Command {}.process_batch(&(), &batch).await.unwrap();
+        let res = res.as_binary::<i32>();
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(res.value(0), hex!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"));
+        assert_eq!(res.value(1), hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"));
+    }
+}
@@ -1,6 +1,11 @@
 mod evm_decode_event;
 mod evm_decode_call;
 mod evm_decode_calldata;
+mod evm_decode_constructor;
+mod evm_decode_trace;
+mod evm_abi_decode;
+mod evm_abi_encode;
+mod evm_encode_calldata;
 mod ethereum_decode_tx;
 mod ethereum_rpc;
 mod ethereum_rpc_call;
@@ -11,9 +16,15 @@ mod convert_format;
 
 use clap::{Args, Subcommand};
 use anyhow::Result;
+use crate::cli::utils::{run_udf, UdfArgs};
 use evm_decode_event::EVMDecodeEventCommand;
 use evm_decode_call::EVMDecodeCallCommand;
 use evm_decode_calldata::EVMDecodeCalldataCommand;
+use evm_decode_constructor::EVMDecodeConstructorCommand;
+use evm_decode_trace::EVMDecodeTraceCommand;
+use evm_abi_decode::EVMAbiDecodeCommand;
+use evm_abi_encode::EVMAbiEncodeCommand;
+use evm_encode_calldata::EVMEncodeCalldataCommand;
 use ethereum_decode_tx::EthereumDecodeTxCommand;
 use ethereum_rpc::EthereumRPCCommand;
 use ethereum_rpc_call::EthereumRPCCallCommand;
@@ -27,6 +38,11 @@ pub enum FunctionCommand {
     EVMDecodeEvent(EVMDecodeEventCommand),
     EVMDecodeCall(EVMDecodeCallCommand),
     EVMDecodeCalldata(EVMDecodeCalldataCommand),
+    EVMDecodeConstructor(EVMDecodeConstructorCommand),
+    EVMDecodeTrace(EVMDecodeTraceCommand),
+    EVMAbiDecode(EVMAbiDecodeCommand),
+    EVMAbiEncode(EVMAbiEncodeCommand),
+    EVMEncodeCalldata(EVMEncodeCalldataCommand),
     EthereumDecodeTx(EthereumDecodeTxCommand),
     EthereumRPC(EthereumRPCCommand),
     EthereumRPCCall(EthereumRPCCallCommand),
@@ -38,6 +54,9 @@ pub enum FunctionCommand {
 
 #[derive(Clone, Debug, Args)]
 pub struct Function {
+    #[command(flatten)]
+    pub udf_args: UdfArgs,
+
     #[command(subcommand)]
     pub cmd: FunctionCommand
 }
@@ -45,16 +64,21 @@ pub struct Function {
 impl Function {
     pub async fn run(&self) -> Result<()> {
         match &self.cmd {
-            FunctionCommand::EVMDecodeEvent(cmd) => cmd.run().await,
-            FunctionCommand::EVMDecodeCall(cmd) => cmd.run().await,
-            FunctionCommand::EVMDecodeCalldata(cmd) => cmd.run().await,
-            FunctionCommand::EthereumDecodeTx(cmd) => cmd.run().await,
-            FunctionCommand::EthereumRPC(cmd) => cmd.run().await,
-            FunctionCommand::EthereumRPCCall(cmd) => cmd.run().await,
-            FunctionCommand::EVMDescriptorFromFullsig(cmd) => cmd.run().await,
-            FunctionCommand::EVMSignatureFromDescriptor(cmd) => cmd.run().await,
-            FunctionCommand::Keccak256(cmd) => cmd.run().await,
-            FunctionCommand::ConvertFormat(cmd) => cmd.run().await
+            FunctionCommand::EVMDecodeEvent(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMDecodeCall(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMDecodeCalldata(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMDecodeConstructor(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMDecodeTrace(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMAbiDecode(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMAbiEncode(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMEncodeCalldata(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EthereumDecodeTx(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EthereumRPC(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EthereumRPCCall(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMDescriptorFromFullsig(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::EVMSignatureFromDescriptor(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::Keccak256(cmd) => run_udf(cmd, &self.udf_args).await,
+            FunctionCommand::ConvertFormat(cmd) => run_udf(cmd, &self.udf_args).await
         }
     }
 }
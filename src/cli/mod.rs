@@ -1,31 +1,43 @@
 mod function;
+mod serve_mock;
 mod utils;
 
 use clap::{Subcommand,Parser};
 use anyhow::Result;
 use tokio::runtime::Builder;
 use crate::cli::function::Function;
+use crate::cli::serve_mock::ServeMockCommand;
+use crate::cli::utils::{init_logging,handle_termination_signals};
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
-    Function(Function)
+    Function(Function),
+    ServeMock(ServeMockCommand)
 }
 
 #[derive(Parser)]
 pub struct CLI {
+    #[arg(long, global = true, env = "CH_EVM_LOG", default_value = "warn")]
+    pub log_level: String,
+
     #[command(subcommand)]
     pub cmd: Command,
 }
 
 impl CLI {
     pub fn run(&self) -> Result<()> {
+        init_logging(&self.log_level)?;
+
         Builder::new_multi_thread()
             .enable_time()
             .enable_io()
             .build()?
             .block_on(async {
+                handle_termination_signals()?;
+
                 match &self.cmd {
                     Command::Function(cmd) => cmd.run().await,
+                    Command::ServeMock(cmd) => cmd.run().await,
                 }
             })
     }
@@ -0,0 +1,321 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use http_body_util::{BodyExt, Full};
+use hyper::{Request, Response, StatusCode};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use serde_inline_default::serde_inline_default;
+use duration_str::deserialize_duration;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockFixture {
+    #[serde(deserialize_with = "deserialize_duration")]
+    #[serde_inline_default(Duration::ZERO)]
+    latency: Duration,
+
+    #[serde(alias = "max-batch-size")]
+    #[serde(default)]
+    max_batch_size: Option<usize>,
+
+    #[serde(alias = "http-errors")]
+    #[serde(default)]
+    http_errors: Vec<MockHttpError>,
+
+    #[serde(default)]
+    responses: Vec<MockResponse>
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockHttpError {
+    status: u16,
+    count: usize
+}
+
+#[serde_inline_default]
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockResponse {
+    method: String,
+
+    #[serde(default)]
+    params: Option<Value>,
+
+    #[serde(default)]
+    result: Value,
+
+    #[serde(default)]
+    error: Option<Value>,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    #[serde_inline_default(Duration::ZERO)]
+    latency: Duration
+}
+
+impl MockFixture {
+    pub fn from_file(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path).context("cannot read fixture file")?;
+        serde_yaml::from_str(&content).context("cannot parse fixture file")
+    }
+
+    fn find_response(&self, method: &str, params: &Value) -> Option<&MockResponse> {
+        self.responses
+            .iter()
+            .filter(|r| r.method == method)
+            .find(|r| r.params.as_ref().is_none_or(|p| p == params))
+    }
+
+    fn http_error(&self, request_index: usize) -> Option<u16> {
+        let mut upper = 0;
+
+        for e in &self.http_errors {
+            upper += e.count;
+
+            if request_index < upper {
+                return Some(e.status);
+            }
+        }
+
+        None
+    }
+}
+
+pub struct MockServer {
+    fixture: MockFixture,
+    requests: AtomicUsize
+}
+
+impl MockServer {
+    pub fn new(fixture: MockFixture) -> Self {
+        MockServer { fixture, requests: AtomicUsize::new(0) }
+    }
+
+    pub async fn serve_on(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let this = self.clone();
+
+            tokio::spawn(async move {
+                let svc = service_fn(|req| {
+                    let this = this.clone();
+                    async move { this.handle(req).await }
+                });
+
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), svc)
+                    .await;
+            });
+        }
+    }
+
+    async fn handle(&self, req: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+        let request_index = self.requests.fetch_add(1, Ordering::SeqCst);
+
+        if let Some(status) = self.fixture.http_error(request_index) {
+            sleep(self.fixture.latency).await;
+            return Ok(status_response(status));
+        }
+
+        let body = match req.into_body().collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(_) => return Ok(json_response(error_object(Value::Null, -32700, "cannot read request body")))
+        };
+
+        let (res, latency) = self.handle_body(&body);
+
+        sleep(self.fixture.latency + latency).await;
+        Ok(json_response(res))
+    }
+
+    fn handle_body(&self, body: &[u8]) -> (Value, Duration) {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(calls)) => self.handle_batch(&calls),
+            Ok(call) => self.handle_call(&call),
+            Err(_) => (error_object(Value::Null, -32700, "parse error"), Duration::ZERO)
+        }
+    }
+
+    fn handle_batch(&self, calls: &[Value]) -> (Value, Duration) {
+        // An empty batch is an invalid request, answered with a single error and not an empty batch.
+        if calls.is_empty() {
+            return (error_object(Value::Null, -32600, "empty batch"), Duration::ZERO);
+        }
+
+        if self.fixture.max_batch_size.is_some_and(|max| calls.len() > max) {
+            return (error_object(Value::Null, -32600, "batch too large"), Duration::ZERO);
+        }
+
+        let mut latency = Duration::ZERO;
+        let mut results = Vec::with_capacity(calls.len());
+
+        for call in calls {
+            let (res, l) = self.handle_call(call);
+            latency = latency.max(l);
+            results.push(res);
+        }
+
+        (Value::Array(results), latency)
+    }
+
+    fn handle_call(&self, call: &Value) -> (Value, Duration) {
+        let id = call["id"].clone();
+
+        let Some(method) = call["method"].as_str() else {
+            return (error_object(id, -32600, "invalid request"), Duration::ZERO)
+        };
+
+        let params = match &call["params"] {
+            Value::Null => json!([]),
+            params => params.clone()
+        };
+
+        match self.fixture.find_response(method, &params) {
+            None => (error_object(id, -32601, &format!("method {} not found", method)), Duration::ZERO),
+            Some(MockResponse { error: Some(err), latency, .. }) =>
+                (json!({"jsonrpc": "2.0", "id": id, "error": err}), *latency),
+            Some(MockResponse { result, latency, .. }) =>
+                (json!({"jsonrpc": "2.0", "id": id, "result": result}), *latency)
+        }
+    }
+}
+
+fn error_object(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn json_response(v: Value) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::from(v.to_string())));
+    res.headers_mut().insert(CONTENT_TYPE, "application/json".parse().expect("valid header value"));
+    res
+}
+
+fn status_response(status: u16) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::new()));
+    *res.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use crate::evm::rpc::{RpcCall, RpcClient, RpcResult};
+    use super::*;
+
+    async fn start(fixture: &str) -> (Arc<MockServer>, String) {
+        let fixture: MockFixture = serde_yaml::from_str(fixture).unwrap();
+        let server = Arc::new(MockServer::new(fixture));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(server.clone().serve_on(listener));
+        (server, url)
+    }
+
+    fn call(method: &str) -> RpcCall {
+        RpcCall { method: method.to_string(), params: vec![] }
+    }
+
+    const FIXTURE: &str = r#"
+responses:
+  - method: eth_blockNumber
+    result: "0x10"
+  - method: eth_getBlockByNumber
+    result: null
+  - method: eth_call
+    error:
+      code: 3
+      message: execution reverted
+"#;
+
+    #[tokio::test]
+    async fn retries_retryable_status_codes() {
+        let (server, url) = start(&format!("http-errors:\n  - status: 429\n    count: 2\n{FIXTURE}")).await;
+        let client = RpcClient::new(&format!("{url}#retry-initial-interval=10ms&retry-randomization-factor=0")).unwrap();
+
+        let start = Instant::now();
+        let res = client.calls([call("eth_blockNumber")]).await.unwrap();
+
+        assert!(matches!(&res[..], [RpcResult::Value(v)] if v == "0x10"));
+        assert_eq!(server.requests.load(Ordering::SeqCst), 3);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_tries() {
+        let (server, url) = start(&format!("http-errors:\n  - status: 503\n    count: 10\n{FIXTURE}")).await;
+        let client = RpcClient::new(&format!("{url}#retry-initial-interval=1ms&retry-max-tries=2")).unwrap();
+
+        assert!(client.calls([call("eth_blockNumber")]).await.is_err());
+        assert_eq!(server.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_status_codes() {
+        let (server, url) = start(&format!("http-errors:\n  - status: 500\n    count: 1\n{FIXTURE}")).await;
+        let client = RpcClient::new(&url).unwrap();
+
+        assert!(client.calls([call("eth_blockNumber")]).await.is_err());
+        assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn fail_on_error() {
+        let (_, url) = start(FIXTURE).await;
+
+        let client = RpcClient::new(&url).unwrap();
+        let res = client.calls([call("eth_call")]).await.unwrap();
+        assert!(matches!(&res[..], [RpcResult::Error(e)] if e.contains("execution reverted")));
+
+        let client = RpcClient::new(&format!("{url}#fail-on-error=true")).unwrap();
+        assert!(client.calls([call("eth_call")]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fail_on_null() {
+        let (_, url) = start(FIXTURE).await;
+
+        let client = RpcClient::new(&url).unwrap();
+        let res = client.calls([call("eth_getBlockByNumber")]).await.unwrap();
+        assert!(matches!(&res[..], [RpcResult::Value(Value::Null)]));
+
+        let client = RpcClient::new(&format!("{url}#fail-on-null=true")).unwrap();
+        assert!(client.calls([call("eth_getBlockByNumber")]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn splits_batches() {
+        let (server, url) = start(&format!("max-batch-size: 2\n{FIXTURE}")).await;
+        let client = RpcClient::new(&format!("{url}#max-batch-size=2")).unwrap();
+
+        let res = client.calls((0..5).map(|_| call("eth_blockNumber"))).await.unwrap();
+
+        assert_eq!(res.len(), 5);
+        assert!(res.iter().all(|r| matches!(r, RpcResult::Value(v) if v == "0x10")));
+        assert_eq!(server.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_batches_above_max_batch_size() {
+        let (_, url) = start(&format!("max-batch-size: 2\n{FIXTURE}")).await;
+        let client = RpcClient::new(&format!("{url}#max-batch-size=3")).unwrap();
+
+        assert!(client.calls((0..3).map(|_| call("eth_blockNumber"))).await.is_err());
+    }
+
+    #[test]
+    fn rejects_empty_batches() {
+        let server = MockServer::new(serde_yaml::from_str(FIXTURE).unwrap());
+        let (res, _) = server.handle_body(b"[]");
+
+        assert_eq!(res["error"]["code"], -32600);
+        assert!(res.is_object());
+    }
+}
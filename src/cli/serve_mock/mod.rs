@@ -0,0 +1,30 @@
+mod mock_server;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use clap::Args;
+use tokio::net::TcpListener;
+pub use mock_server::{MockFixture, MockServer};
+
+#[derive(Debug, Clone, Args)]
+pub struct ServeMockCommand {
+    #[arg(short, long, default_value = "127.0.0.1:8545")]
+    listen_address: String,
+
+    #[arg(short, long)]
+    fixture_file: String
+}
+
+impl ServeMockCommand {
+    pub async fn run(&self) -> Result<()> {
+        let addr: SocketAddr = self.listen_address.parse().context("invalid listen address")?;
+        let fixture = MockFixture::from_file(&self.fixture_file)?;
+
+        let listener = TcpListener::bind(addr).await.context("cannot bind listen address")?;
+
+        // printed regardless of the log level, the server is useless until one knows where it listens
+        eprintln!("mock JSON-RPC server listening on {}", listener.local_addr()?);
+        Arc::new(MockServer::new(fixture)).serve_on(listener).await
+    }
+}
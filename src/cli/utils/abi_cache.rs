@@ -0,0 +1,190 @@
+use std::str;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use clap::Args;
+use quick_cache::sync::Cache;
+use tracing::debug;
+use alloy::transports::http::reqwest;
+use crate::evm::abi::{AbiContext, AbiItemProvider, AbiItemProviderFactory, DocumentCache, DocumentNotFound};
+use super::rpc_stats::parse_duration;
+
+pub type SharedAbiItemProvider = Arc<dyn AbiItemProvider + Send + Sync>;
+
+#[derive(Debug, Clone, Args)]
+pub struct AbiCacheArgs {
+    #[arg(short, long, default_value_t = 2000)]
+    abi_provider_cache_size: usize,
+
+    // Directory where fetched ABI documents are kept, shared by all the workers.
+    #[arg(long, env = "CH_EVM_ABI_CACHE_DIR")]
+    abi_cache_dir: Option<PathBuf>,
+
+    // How long an ABI is used before being revalidated.
+    #[arg(long, value_parser = parse_duration, default_value = "1h")]
+    abi_cache_ttl: Duration,
+
+    // How long a decoder whose ABI does not exist, e.g. a URL returning 404, is not retried.
+    #[arg(long, value_parser = parse_duration, default_value = "5m")]
+    abi_cache_negative_ttl: Duration
+}
+
+impl AbiCacheArgs {
+    pub fn build(&self) -> Result<AbiItemProviderCache> {
+        Ok(AbiItemProviderCache {
+            providers: Arc::new(Cache::new(self.abi_provider_cache_size)),
+            docs: Arc::new(DocumentCache::new(self.abi_cache_dir.clone(), self.abi_cache_ttl, self.abi_cache_negative_ttl)?),
+            ttl: self.abi_cache_ttl,
+            negative_ttl: self.abi_cache_negative_ttl
+        })
+    }
+}
+
+// A provider, or the error raised when creating it, and when it must be created again.
+#[derive(Clone)]
+struct CachedAbiItemProvider {
+    provider: Result<SharedAbiItemProvider, Arc<anyhow::Error>>,
+    expires_at: Instant
+}
+
+#[derive(Clone)]
+pub struct AbiItemProviderCache {
+    providers: Arc<Cache<String, CachedAbiItemProvider>>,
+    docs: Arc<DocumentCache>,
+    ttl: Duration,
+    negative_ttl: Duration
+}
+
+impl AbiItemProviderCache {
+    pub fn hit_ratio(&self) -> f64 {
+        self.providers.hits() as f64 / (self.providers.hits() + self.providers.misses()).max(1) as f64
+    }
+}
+
+// Gets the provider of a decoder for the given context, which must only hold what the provider depends on, see
+// `AbiItemProviderFactory::context_scope`.
+pub async fn get_cached_abi_item_provider(
+    cache: &AbiItemProviderCache,
+    key: &[u8],
+    ctx: &AbiContext
+) -> Result<SharedAbiItemProvider> {
+    let key = str::from_utf8(key)?;
+    let cache_key = match *ctx == AbiContext::default() {
+        true => key.to_string(),
+        false => format!("{}@{}", key, ctx)
+    };
+
+    loop {
+        match cache.providers.get_value_or_guard_async(&cache_key).await {
+            Ok(cached) if cached.expires_at > Instant::now() => {
+                return cached.provider.map_err(|err| anyhow!("{err:#}"));
+            },
+            Ok(_) => {
+                cache.providers.remove(&cache_key);
+            },
+            Err(guard) => {
+                let provider = AbiItemProviderFactory::create(key, ctx, &cache.docs).await
+                    .inspect_err(|err| debug!(decoder = key, error = %err, "cannot create ABI item provider"));
+
+                // Transient failures are not cached: dropping the guard lets the next row try again.
+                let ttl = match &provider {
+                    Ok(_) => cache.ttl,
+                    Err(err) if is_definitive_miss(err) => cache.negative_ttl,
+                    Err(err) => return Err(anyhow!("{err:#}"))
+                };
+                let provider = provider.map_err(Arc::new);
+
+                let _ = guard.insert(CachedAbiItemProvider { provider: provider.clone(), expires_at: Instant::now() + ttl });
+                return provider.map_err(|err| anyhow!("{err:#}"));
+            }
+        }
+    }
+}
+
+// Whether a provider cannot be created because its ABI does not exist, e.g. a missing document, an HTTP 4xx or
+// a contract which is not verified, as opposed to a failure which may not happen again, e.g. a timeout or a 5xx.
+fn is_definitive_miss(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        if e.is::<DocumentNotFound>() {
+            return true;
+        }
+
+        if let Some(object_store::Error::NotFound { .. }) = e.downcast_ref::<object_store::Error>() {
+            return true;
+        }
+
+        e.downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status)
+            .is_some_and(|status| status.is_client_error() && !matches!(status.as_u16(), 408 | 429))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use super::*;
+
+    // The error of a request to a server answering with the given status.
+    async fn http_error(status: u16) -> anyhow::Error {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 4096]).await;
+            let res = format!("HTTP/1.1 {status} Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+            stream.write_all(res.as_bytes()).await.unwrap();
+        });
+
+        let res = reqwest::get(&url).await.and_then(|res| res.error_for_status());
+        anyhow::Error::from(res.unwrap_err()).context("cannot fetch ABI")
+    }
+
+    #[tokio::test]
+    async fn caches_http_client_errors_only() {
+        assert!(is_definitive_miss(&http_error(404).await));
+        assert!(is_definitive_miss(&http_error(400).await));
+        assert!(!is_definitive_miss(&http_error(429).await));
+        assert!(!is_definitive_miss(&http_error(503).await));
+    }
+
+    #[test]
+    fn caches_definitive_misses_only() {
+        let not_found = object_store::Error::NotFound { path: "abis/erc20.json".to_string(), source: "missing".into() };
+        assert!(is_definitive_miss(&anyhow::Error::from(not_found).context("cannot load ABI")));
+
+        let not_verified = DocumentNotFound("document not found".to_string());
+        assert!(is_definitive_miss(&anyhow::Error::from(not_verified).context("no verified ABI")));
+
+        let unavailable = object_store::Error::Generic { store: "S3", source: "connection reset".into() };
+        assert!(!is_definitive_miss(&anyhow::Error::from(unavailable)));
+
+        let timeout: Result<()> = Err(std::io::Error::from(std::io::ErrorKind::TimedOut)).context("cannot fetch ABI");
+        assert!(!is_definitive_miss(&timeout.unwrap_err()));
+    }
+
+    #[tokio::test]
+    async fn caches_contracts_missing_from_sourcify() {
+        let root = std::env::temp_dir().join(format!("ch_evm_abi_cache_{}_sourcify", std::process::id()));
+        let key = format!("sourcify://{}#chain=1", root.display());
+        let address = alloy::primitives::address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let ctx = AbiContext { address: Some(address), block_number: None };
+        let cache = crate::cli::utils::default_args::<AbiCacheArgs>().build().unwrap();
+
+        assert!(get_cached_abi_item_provider(&cache, key.as_bytes(), &ctx).await.is_err());
+
+        // The contract is verified afterwards: the miss is still cached, until its negative TTL expires.
+        let dir = root.join(format!("contracts/full_match/1/{}", address.to_checksum(None)));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("metadata.json"), r#"{"output": {"abi": []}}"#).unwrap();
+
+        assert!(get_cached_abi_item_provider(&cache, key.as_bytes(), &ctx).await.is_err());
+
+        let cache = crate::cli::utils::default_args::<AbiCacheArgs>().build().unwrap();
+        assert!(get_cached_abi_item_provider(&cache, key.as_bytes(), &ctx).await.is_ok());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
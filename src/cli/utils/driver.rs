@@ -0,0 +1,599 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+use anyhow::{anyhow, bail, Result};
+use arrow::array::{new_null_array, Array, ArrayRef, BinaryArray, BooleanArray, RecordBatch, Scalar, UInt32Array};
+use arrow::compute::{cast, filter_record_batch, take};
+use arrow::compute::kernels::zip::zip;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use clap::{Args, ValueEnum};
+use futures::stream::{poll_fn as stream_poll_fn, StreamExt, TryStreamExt};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::task::spawn_blocking;
+use arrow_ipc::reader::StreamReader;
+use arrow_ipc::writer::StreamWriter;
+use tracing::{debug, info};
+use super::{create_file_or_stdout, open_file_or_stdin};
+
+// A ClickHouse executable UDF: receives blocks of arguments and returns a single result column.
+pub trait ScalarUdf {
+    // Per-process state shared by all the blocks, e.g. caches.
+    type State;
+
+    fn input_columns(&self) -> &[&str];
+
+    fn return_type(&self) -> DataType {
+        DataType::Binary
+    }
+
+    fn init_state(&self) -> Result<Self::State>;
+
+    fn process_batch(&self, state: &Self::State, input_batch: &RecordBatch) -> impl Future<Output = Result<ArrayRef>>;
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum NullHandling {
+    // NULL result for rows with a NULL argument
+    Propagate,
+    // `{"error": "null argument"}` result for rows with a NULL argument
+    Error
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct UdfArgs {
+    #[arg(short, long, global = true, default_value = "")]
+    pub input_file: String,
+
+    #[arg(short, long, global = true, default_value = "")]
+    pub output_file: String,
+
+    #[arg(long, global = true, value_enum, default_value_t = NullHandling::Propagate)]
+    pub null_handling: NullHandling,
+
+    // Number of blocks processed concurrently, e.g. to overlap the RPC calls of consecutive blocks.
+    #[arg(long, global = true, default_value_t = 1)]
+    pub pipeline_depth: usize
+}
+
+pub async fn run_udf<U: ScalarUdf>(udf: &U, args: &UdfArgs) -> Result<()> {
+    let state = udf.init_state()?;
+    let output_schema = Arc::new(Schema::new(vec![
+        Field::new("result", udf.return_type(), true),
+    ]));
+
+    let null_error = match args.null_handling {
+        NullHandling::Propagate => None,
+        NullHandling::Error => Some(null_error(&udf.return_type())?)
+    };
+
+    process_arrow_streams(&args.input_file, &args.output_file, output_schema.clone(), args.pipeline_depth, async |input_batch| {
+        let result_col = process_block(udf, &state, null_error.as_ref(), &input_batch).await?;
+        Ok(RecordBatch::try_new(output_schema.clone(), vec![result_col])?)
+    }).await
+}
+
+// Result of the rows with a NULL argument in the `error` mode, in the return type of the UDF.
+fn null_error(return_type: &DataType) -> Result<Scalar<ArrayRef>> {
+    let err = cast(&BinaryArray::from_vec(vec![b"{\"error\": \"null argument\"}"]), return_type)
+        .ok()
+        .filter(|err| err.is_valid(0))
+        .ok_or_else(|| anyhow!("--null-handling error is not supported for {} results", return_type))?;
+    Ok(Scalar::new(err))
+}
+
+// Only the rows without NULL arguments are passed to the UDF, the other rows get NULL, or `null_error` if set.
+async fn process_block<U: ScalarUdf>(
+    udf: &U,
+    state: &U::State,
+    null_error: Option<&Scalar<ArrayRef>>,
+    input_batch: &RecordBatch
+) -> Result<ArrayRef> {
+    let mut valid = vec![true; input_batch.num_rows()];
+
+    for col_name in udf.input_columns() {
+        let col = input_batch.column_by_name(col_name).ok_or(anyhow!("cannot find column {}", col_name))?;
+
+        if col.null_count() > 0 {
+            valid.iter_mut().enumerate().for_each(|(i, v)| *v &= col.is_valid(i));
+        }
+    }
+
+    if valid.iter().all(|v| *v) {
+        return process_rows(udf, state, input_batch).await;
+    }
+
+    // Results of the valid rows are scattered back to their rows.
+    let valid_batch = filter_record_batch(input_batch, &BooleanArray::from(valid.clone()))?;
+    let valid_result_col = process_rows(udf, state, &valid_batch).await?;
+    let mut next = 0;
+    let indices: UInt32Array = valid
+        .iter()
+        .map(|v| v.then(|| { next += 1; next - 1 }))
+        .collect();
+    let result_col = take(&valid_result_col, &indices, None)?;
+
+    // The UDF may return NULL for valid rows, only the rows with a NULL argument get the error.
+    match null_error {
+        None => Ok(result_col),
+        Some(err) => {
+            let null_args: BooleanArray = valid.iter().map(|v| Some(!v)).collect();
+            Ok(zip(&null_args, err, &result_col)?)
+        }
+    }
+}
+
+async fn process_rows<U: ScalarUdf>(udf: &U, state: &U::State, batch: &RecordBatch) -> Result<ArrayRef> {
+    if batch.num_rows() == 0 {
+        return Ok(new_null_array(&udf.return_type(), 0));
+    }
+
+    let result_col = udf.process_batch(state, batch).await?;
+
+    if result_col.len() != batch.num_rows() {
+        bail!("result has {} rows instead of {}", result_col.len(), batch.num_rows());
+    }
+
+    Ok(result_col)
+}
+
+// Whether a termination signal was received and the number of blocks read but not written yet. Both are
+// updated under the same lock, so that a block is either counted before the signal is handled, or not read at all.
+struct Shutdown {
+    requested: bool,
+    blocks_in_flight: usize
+}
+
+static SHUTDOWN: Mutex<Shutdown> = Mutex::new(Shutdown { requested: false, blocks_in_flight: 0 });
+
+fn shutdown_state() -> MutexGuard<'static, Shutdown> {
+    SHUTDOWN.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Counts a block about to be read, unless a termination signal was received.
+fn start_block() -> bool {
+    let mut state = shutdown_state();
+
+    if state.requested {
+        return false;
+    }
+
+    state.blocks_in_flight += 1;
+    true
+}
+
+fn finish_block() {
+    let mut state = shutdown_state();
+    state.blocks_in_flight -= 1;
+
+    if state.requested && state.blocks_in_flight == 0 {
+        info!("termination signal received, exiting after current blocks");
+        std::process::exit(0);
+    }
+}
+
+enum StreamMessage {
+    // ClickHouse sends each block of arguments as a new Arrow stream, answered by a new output stream.
+    Start,
+    Batch(RecordBatch)
+}
+
+// Reads the Arrow streams sent by ClickHouse one after the other, calls `f` on each batch and writes
+// the result batches back in order. Returns once the input is closed or the output is closed by the reader.
+//
+// Reading, processing and writing run concurrently: up to `pipeline_depth` batches are processed at the same
+// time, and bounded channels between the stages limit the number of batches held in memory.
+pub async fn process_arrow_streams<F>(
+    input_file: &str,
+    output_file: &str,
+    output_schema: SchemaRef,
+    pipeline_depth: usize,
+    f: F
+) -> Result<()>
+where
+    F: AsyncFn(RecordBatch) -> Result<RecordBatch>
+{
+    let input_file = open_file_or_stdin(input_file)?;
+    let output_file = create_file_or_stdout(output_file)?;
+    let pipeline_depth = pipeline_depth.max(1);
+
+    let (input_tx, mut input_rx) = channel(pipeline_depth);
+    let (output_tx, output_rx) = channel(pipeline_depth);
+
+    // The reader is not joined: it may be blocked on a read that never returns when the pipeline fails.
+    thread::spawn(move || read_arrow_streams(input_file, input_tx));
+    let writer = spawn_blocking(move || write_arrow_streams(output_file, output_schema, output_rx));
+
+    let res = stream_poll_fn(|cx| input_rx.poll_recv(cx))
+        .map(|msg: Result<StreamMessage>| {
+            let f = &f;
+
+            async move {
+                match msg? {
+                    StreamMessage::Batch(input_batch) => {
+                        let start = Instant::now();
+                        let rows = input_batch.num_rows();
+                        let output_batch = f(input_batch).await?;
+
+                        debug!(rows, elapsed_ms = start.elapsed().as_millis() as u64, "processed block");
+                        Ok(StreamMessage::Batch(output_batch))
+                    },
+                    msg => Ok(msg)
+                }
+            }
+        })
+        .buffered(pipeline_depth)
+        .try_for_each(async |msg| output_tx.send(msg).await.map_err(|_| anyhow!("output writer stopped")))
+        .await;
+
+    drop(output_tx);
+
+    // A failing writer stops the pipeline, its error is the one to report.
+    let res = match writer.await? {
+        Ok(()) => res,
+        Err(err) => Err(err)
+    };
+
+    match res {
+        Err(err) if is_broken_pipe(&err) => {
+            debug!("output closed, exiting");
+            Ok(())
+        },
+        res => res
+    }
+}
+
+fn read_arrow_streams(input_file: Box<dyn Read + Send>, tx: Sender<Result<StreamMessage>>) {
+    if let Err(err) = try_read_arrow_streams(input_file, &tx) {
+        let _ = tx.blocking_send(Err(err));
+    }
+}
+
+fn try_read_arrow_streams(input_file: Box<dyn Read + Send>, tx: &Sender<Result<StreamMessage>>) -> Result<()> {
+    let mut input_file = BufReader::new(input_file);
+
+    // Every stream starts with a schema message: no bytes left between 2 streams means the input is closed.
+    while !input_file.fill_buf()?.is_empty() {
+        let mut reader = StreamReader::try_new(&mut input_file, None)?;
+
+        if tx.blocking_send(Ok(StreamMessage::Start)).is_err() {
+            return Ok(());
+        }
+
+        loop {
+            // Wait for the next message before counting it: an idle reader must not hold up the shutdown.
+            reader.get_mut().fill_buf()?;
+
+            if !start_block() {
+                info!("termination signal received, not reading more blocks");
+                return Ok(());
+            }
+
+            // A block that is not sent to the pipeline is not going to be written, it is not in flight anymore.
+            let input_batch = match reader.next() {
+                Some(Ok(input_batch)) => input_batch,
+                Some(Err(err)) => {
+                    finish_block();
+                    return Err(err.into());
+                },
+                None => {
+                    finish_block();
+                    break;
+                }
+            };
+
+            if tx.blocking_send(Ok(StreamMessage::Batch(input_batch))).is_err() {
+                finish_block();
+                return Ok(());
+            }
+        }
+    }
+
+    debug!("input closed, exiting");
+    Ok(())
+}
+
+fn write_arrow_streams(
+    mut output_file: Box<dyn Write + Send>,
+    output_schema: SchemaRef,
+    mut rx: Receiver<StreamMessage>
+) -> Result<()> {
+    let mut next = rx.blocking_recv();
+
+    while let Some(msg) = next {
+        let StreamMessage::Start = msg else {
+            bail!("batch received outside of a stream");
+        };
+
+        let mut writer = StreamWriter::try_new_buffered(&mut output_file, &output_schema)?;
+
+        next = loop {
+            match rx.blocking_recv() {
+                Some(StreamMessage::Batch(output_batch)) => {
+                    writer.write(&output_batch)?;
+                    writer.flush()?;
+                    finish_block();
+                },
+                msg => break msg
+            }
+        };
+    }
+
+    output_file.flush()?;
+    Ok(())
+}
+
+// Exits on SIGTERM / SIGINT once the blocks being processed, if any, have been written.
+#[cfg(unix)]
+pub fn handle_termination_signals() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = sigterm.recv() => (),
+            _ = sigint.recv() => ()
+        }
+
+        let mut state = shutdown_state();
+        state.requested = true;
+
+        if state.blocks_in_flight == 0 {
+            info!("termination signal received, exiting");
+            std::process::exit(0);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn handle_termination_signals() -> Result<()> {
+    Ok(())
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            return e.kind() == ErrorKind::BrokenPipe;
+        }
+
+        matches!(e.downcast_ref::<ArrowError>(), Some(ArrowError::IoError(_, e)) if e.kind() == ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use arrow::array::AsArray;
+    use super::*;
+
+    fn binary_batch(name: &str, values: Vec<Option<&[u8]>>) -> RecordBatch {
+        RecordBatch::try_from_iter([(name, Arc::new(BinaryArray::from(values)) as ArrayRef)]).unwrap()
+    }
+
+    fn arrow_stream(batches: &[RecordBatch]) -> Vec<u8> {
+        let mut w = StreamWriter::try_new(Vec::new(), &batches[0].schema()).unwrap();
+        for batch in batches {
+            w.write(batch).unwrap();
+        }
+        w.into_inner().unwrap()
+    }
+
+    // Concatenates its `a` and `b` arguments, or returns what `f` returns when set.
+    struct TestUdf {
+        return_type: DataType,
+        f: Option<fn(&RecordBatch) -> Result<ArrayRef>>
+    }
+
+    impl TestUdf {
+        fn new() -> Self {
+            Self { return_type: DataType::Binary, f: None }
+        }
+    }
+
+    impl ScalarUdf for TestUdf {
+        type State = ();
+
+        fn input_columns(&self) -> &[&str] {
+            &["a", "b"]
+        }
+
+        fn return_type(&self) -> DataType {
+            self.return_type.clone()
+        }
+
+        fn init_state(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn process_batch(&self, _: &(), input_batch: &RecordBatch) -> Result<ArrayRef> {
+            if let Some(f) = self.f {
+                return f(input_batch);
+            }
+
+            let a: &BinaryArray = input_batch.column(0).as_any().downcast_ref().unwrap();
+            let b: &BinaryArray = input_batch.column(1).as_any().downcast_ref().unwrap();
+            let res: BinaryArray = a.iter().zip(b.iter())
+                .map(|(a, b)| Some([a.unwrap(), b.unwrap()].concat()))
+                .collect();
+
+            Ok(cast(&res, &self.return_type)?)
+        }
+    }
+
+    fn args_batch(a: Vec<Option<&[u8]>>, b: Vec<Option<&[u8]>>) -> RecordBatch {
+        RecordBatch::try_from_iter([
+            ("a", Arc::new(BinaryArray::from(a)) as ArrayRef),
+            ("b", Arc::new(BinaryArray::from(b)) as ArrayRef)
+        ]).unwrap()
+    }
+
+    fn values(col: &ArrayRef) -> Vec<Option<&[u8]>> {
+        col.as_any().downcast_ref::<BinaryArray>().unwrap().iter().collect()
+    }
+
+    #[tokio::test]
+    async fn processes_blocks() {
+        let batch = args_batch(vec![Some(b"a"), Some(b"b")], vec![Some(b"1"), Some(b"2")]);
+        let res = process_block(&TestUdf::new(), &(), None, &batch).await.unwrap();
+
+        assert_eq!(values(&res), [Some(b"a1".as_slice()), Some(b"b2")]);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_blocks() {
+        let missing_column = RecordBatch::try_from_iter([
+            ("a", Arc::new(BinaryArray::from(vec![b"a".as_slice()])) as ArrayRef)
+        ]).unwrap();
+        let err = process_block(&TestUdf::new(), &(), None, &missing_column).await.unwrap_err();
+        assert_eq!(err.to_string(), "cannot find column b");
+
+        let udf = TestUdf { f: Some(|_| Ok(Arc::new(BinaryArray::from(vec![b"x".as_slice()])))), ..TestUdf::new() };
+        let batch = args_batch(vec![Some(b"a"), Some(b"b")], vec![Some(b"1"), Some(b"2")]);
+        let err = process_block(&udf, &(), None, &batch).await.unwrap_err();
+        assert_eq!(err.to_string(), "result has 1 rows instead of 2");
+    }
+
+    const NULL_ERROR: &[u8] = b"{\"error\": \"null argument\"}";
+
+    #[tokio::test]
+    async fn propagates_null_arguments() {
+        let batch = args_batch(vec![Some(b"a"), None, Some(b"c"), Some(b"d")], vec![Some(b"1"), Some(b"2"), None, Some(b"4")]);
+        let res = process_block(&TestUdf::new(), &(), None, &batch).await.unwrap();
+
+        assert_eq!(values(&res), [Some(b"a1".as_slice()), None, None, Some(b"d4")]);
+    }
+
+    #[tokio::test]
+    async fn returns_errors_for_null_arguments() {
+        let err = null_error(&DataType::Binary).unwrap();
+        let batch = args_batch(vec![Some(b"a"), None, Some(b"c")], vec![Some(b"1"), Some(b"2"), None]);
+        let res = process_block(&TestUdf::new(), &(), Some(&err), &batch).await.unwrap();
+        assert_eq!(values(&res), [Some(b"a1".as_slice()), Some(NULL_ERROR), Some(NULL_ERROR)]);
+
+        let batch = args_batch(vec![None, None], vec![Some(b"1"), None]);
+        let res = process_block(&TestUdf::new(), &(), Some(&err), &batch).await.unwrap();
+        assert_eq!(values(&res), [Some(NULL_ERROR), Some(NULL_ERROR)]);
+    }
+
+    #[tokio::test]
+    async fn keeps_null_results_of_valid_rows() {
+        let udf = TestUdf { f: Some(|batch| Ok(new_null_array(&DataType::Binary, batch.num_rows()))), ..TestUdf::new() };
+        let err = null_error(&DataType::Binary).unwrap();
+        let batch = args_batch(vec![Some(b"a"), None], vec![Some(b"1"), Some(b"2")]);
+        let res = process_block(&udf, &(), Some(&err), &batch).await.unwrap();
+
+        assert_eq!(values(&res), [None, Some(NULL_ERROR)]);
+    }
+
+    #[tokio::test]
+    async fn returns_errors_in_the_return_type() {
+        let udf = TestUdf { return_type: DataType::Utf8, f: None };
+        let err = null_error(&DataType::Utf8).unwrap();
+        let batch = args_batch(vec![Some(b"a"), None], vec![Some(b"1"), Some(b"2")]);
+        let res = process_block(&udf, &(), Some(&err), &batch).await.unwrap();
+        let res: Vec<_> = res.as_string::<i32>().iter().collect();
+
+        assert_eq!(res, [Some("a1"), Some("{\"error\": \"null argument\"}")]);
+        assert!(null_error(&DataType::Int64).is_err());
+    }
+
+    // Tests reading blocks update the shared count of blocks in flight, a failing pipeline leaving its blocks
+    // counted.
+    static STREAMS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("ch_evm_driver_{}_{name}", std::process::id())).to_str().unwrap().to_string()
+    }
+
+    // The output streams have no end marker, they are split at their schema messages to be read.
+    fn read_output_streams(path: &str) -> Vec<Vec<RecordBatch>> {
+        let output = std::fs::read(path).unwrap();
+        let mut starts = Vec::new();
+        let mut i = 0;
+
+        while i < output.len() {
+            let len = u32::from_le_bytes(output[i + 4..i + 8].try_into().unwrap()) as usize;
+            let message = arrow_ipc::root_as_message(&output[i + 8..i + 8 + len]).unwrap();
+
+            if message.header_type() == arrow_ipc::MessageHeader::Schema {
+                starts.push(i);
+            }
+
+            i += 8 + len + message.bodyLength() as usize;
+        }
+
+        starts.push(output.len());
+        starts.windows(2)
+            .map(|w| StreamReader::try_new(&output[w[0]..w[1]], None).unwrap().collect::<Result<Vec<_>, _>>().unwrap())
+            .collect()
+    }
+
+    fn first_value(batch: &RecordBatch) -> u8 {
+        batch.column(0).as_binary::<i32>().value(0)[0]
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pipelines_blocks_in_order() {
+        let _streams = STREAMS.lock().await;
+        let in_flight = shutdown_state().blocks_in_flight;
+        let blocks: Vec<RecordBatch> = (0..6u8).map(|i| binary_batch("data", vec![Some(&[i])])).collect();
+        let (input_path, output_path) = (temp_path("pipeline.in"), temp_path("pipeline.out"));
+        std::fs::write(&input_path, [arrow_stream(&blocks[..2]), arrow_stream(&blocks[2..3]), arrow_stream(&blocks[3..])].concat()).unwrap();
+
+        // The first blocks are the slowest: with a deep enough pipeline, the last ones are processed first.
+        let processed = Mutex::new(Vec::new());
+        process_arrow_streams(&input_path, &output_path, blocks[0].schema(), 6, async |batch| {
+            let i = first_value(&batch);
+            tokio::time::sleep(std::time::Duration::from_millis(50 * (6 - i as u64))).await;
+            processed.lock().unwrap().push(i);
+            Ok(batch)
+        }).await.unwrap();
+
+        let streams = read_output_streams(&output_path);
+        let streams: Vec<Vec<u8>> = streams.iter().map(|s| s.iter().map(first_value).collect()).collect();
+        assert_eq!(streams, [vec![0, 1], vec![2], vec![3, 4, 5]]);
+        assert_ne!(processed.into_inner().unwrap(), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(shutdown_state().blocks_in_flight, in_flight);
+
+        std::fs::remove_file(input_path).unwrap();
+        std::fs::remove_file(output_path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stops_on_the_first_error() {
+        let _streams = STREAMS.lock().await;
+        let blocks: Vec<RecordBatch> = (0..3u8).map(|i| binary_batch("data", vec![Some(&[i])])).collect();
+        let (input_path, output_path) = (temp_path("error.in"), temp_path("error.out"));
+        std::fs::write(&input_path, arrow_stream(&blocks)).unwrap();
+
+        let err = process_arrow_streams(&input_path, &output_path, blocks[0].schema(), 2, async |batch| {
+            match first_value(&batch) {
+                1 => bail!("cannot process block"),
+                _ => Ok(batch)
+            }
+        }).await.unwrap_err();
+        assert_eq!(err.to_string(), "cannot process block");
+
+        std::fs::remove_file(input_path).unwrap();
+        std::fs::remove_file(output_path).unwrap();
+    }
+
+    #[test]
+    fn does_not_count_a_block_that_cannot_be_read() {
+        let _streams = STREAMS.blocking_lock();
+        let in_flight = shutdown_state().blocks_in_flight;
+        let mut input = arrow_stream(&[binary_batch("data", vec![Some(&[0xaa; 64]); 16])]);
+        input.truncate(input.len() - 512);
+        let (tx, mut rx) = channel(4);
+
+        assert!(try_read_arrow_streams(Box::new(Cursor::new(input)), &tx).is_err());
+        assert_eq!(shutdown_state().blocks_in_flight, in_flight);
+        assert!(matches!(rx.try_recv(), Ok(Ok(StreamMessage::Start))));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,16 @@
+use anyhow::{anyhow, Result};
+use tracing_subscriber::EnvFilter;
+use super::rpc_stats::STATS_TARGET;
+
+// ClickHouse captures stderr line by line and timestamps it, so keep each event on a single
+// line without colors nor time.
+pub fn init_logging(filter: &str) -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(filter)?.add_directive(format!("{STATS_TARGET}=info").parse()?))
+        .with_writer(std::io::stderr)
+        .with_ansi(false)
+        .without_time()
+        .compact()
+        .try_init()
+        .map_err(|e| anyhow!(e))
+}
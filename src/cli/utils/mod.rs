@@ -1,13 +1,33 @@
+mod rpc_stats;
+mod logging;
+mod driver;
+mod abi_cache;
+
 use std::str;
 use std::io::{stdin,stdout,Read,Write};
 use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
-use anyhow::{anyhow, bail, Result};
-use arrow::array::{ArrayRef,RecordBatch};
+use anyhow::{anyhow, bail, Context, Result};
+use arrow::array::{Array,ArrayRef,AsArray,BinaryArray,BinaryBuilder,Int64Array,ListArray,RecordBatch};
+use arrow::compute::{can_cast_types,cast_with_options,concat,CastOptions};
+use arrow::datatypes::{DataType,Int64Type};
+use futures::stream::{iter, StreamExt};
 use quick_cache::sync::Cache;
-use crate::evm::abi::{AbiItemProvider,AbiItemProviderFactory, Function};
+use rayon::current_num_threads;
+use rayon::prelude::*;
+use tokio::task::block_in_place;
+use tracing::debug;
+use alloy::primitives::Address;
+use crate::evm::abi::{AbiContext,AbiItemProviderFactory,ContextScope,Function};
+
+pub use rpc_stats::RpcStatsArgs;
+pub use logging::init_logging;
+pub use driver::{ScalarUdf,UdfArgs,run_udf,handle_termination_signals};
+pub use abi_cache::{AbiCacheArgs,AbiItemProviderCache,SharedAbiItemProvider,get_cached_abi_item_provider};
 
-pub fn open_file_or_stdin(path: &str) -> Result<Box<dyn Read>> {
+pub fn open_file_or_stdin(path: &str) -> Result<Box<dyn Read + Send>> {
     if path.is_empty() {
         Ok(Box::new(stdin()))
     } else {
@@ -18,7 +38,7 @@ pub fn open_file_or_stdin(path: &str) -> Result<Box<dyn Read>> {
     }
 }
 
-pub fn create_file_or_stdout(path: &str) -> Result<Box<dyn Write>> {
+pub fn create_file_or_stdout(path: &str) -> Result<Box<dyn Write + Send>> {
     if path.is_empty() {
         Ok(Box::new(stdout()))
     } else {
@@ -29,16 +49,68 @@ pub fn create_file_or_stdout(path: &str) -> Result<Box<dyn Write>> {
     }
 }
 
+pub fn binary_result_builder(num_rows: usize) -> BinaryBuilder {
+    BinaryBuilder::with_capacity(num_rows, num_rows * 1024)
+}
 
+// Input columns are cast to a canonical type, so that the functions accept whatever Arrow encoding ClickHouse
+// picked for the arguments: String as Binary or Utf8, LowCardinality as a dictionary, FixedString as
+// FixedSizeBinary, large or view variants...
 pub trait RecordBatchExt {
-    fn get_column<T: 'static>(&self, col_name: &str) -> Result<&T>;
+    fn get_binary_column(&self, col_name: &str) -> Result<BinaryArray>;
+    fn get_optional_binary_column(&self, col_name: &str) -> Result<Option<BinaryArray>>;
+    fn get_binary_list_column(&self, col_name: &str) -> Result<ListArray>;
+    fn get_int64_column(&self, col_name: &str) -> Result<Int64Array>;
+    fn get_optional_int64_column(&self, col_name: &str) -> Result<Option<Int64Array>>;
 }
 
 impl RecordBatchExt for RecordBatch {
-    fn get_column<T: 'static>(&self, col_name: &str) -> Result<&T> {
+    fn get_binary_column(&self, col_name: &str) -> Result<BinaryArray> {
+        Ok(self.cast_column(col_name, &DataType::Binary)?.as_binary::<i32>().clone())
+    }
+
+    fn get_optional_binary_column(&self, col_name: &str) -> Result<Option<BinaryArray>> {
+        match self.column_by_name(col_name) {
+            Some(_) => Ok(Some(self.get_binary_column(col_name)?)),
+            None => Ok(None)
+        }
+    }
+
+    fn get_binary_list_column(&self, col_name: &str) -> Result<ListArray> {
+        let list_type = DataType::new_list(DataType::Binary, true);
+        Ok(self.cast_column(col_name, &list_type)?.as_list::<i32>().clone())
+    }
+
+    fn get_int64_column(&self, col_name: &str) -> Result<Int64Array> {
+        Ok(self.cast_column(col_name, &DataType::Int64)?.as_primitive::<Int64Type>().clone())
+    }
+
+    fn get_optional_int64_column(&self, col_name: &str) -> Result<Option<Int64Array>> {
+        match self.column_by_name(col_name) {
+            Some(_) => Ok(Some(self.get_int64_column(col_name)?)),
+            None => Ok(None)
+        }
+    }
+}
+
+trait CastColumn {
+    fn cast_column(&self, col_name: &str, to_type: &DataType) -> Result<ArrayRef>;
+}
+
+impl CastColumn for RecordBatch {
+    fn cast_column(&self, col_name: &str, to_type: &DataType) -> Result<ArrayRef> {
         let col = self.column_by_name(col_name).ok_or(anyhow!(format!("cannot find column {}", &col_name)))?;
-        let arr = col.as_any().downcast_ref().ok_or(anyhow!(format!("cannot downcast column {}", &col_name)));
-        arr
+
+        if col.data_type() == to_type {
+            return Ok(col.clone());
+        }
+
+        if !can_cast_types(col.data_type(), to_type) {
+            bail!("column {} has type {} and cannot be read as {}", col_name, col.data_type(), to_type);
+        }
+
+        cast_with_options(col, to_type, &CastOptions { safe: false, ..Default::default() })
+            .with_context(|| format!("cannot read column {} as {}", col_name, to_type))
     }
 }
 
@@ -52,16 +124,140 @@ impl ArrayRefExt for ArrayRef {
     }
 }
 
-pub async fn get_cached_abi_item_provider(
-    cache: Arc<Cache<String, Arc<dyn AbiItemProvider + Send + Sync>>>,
-    key: &[u8]
-) -> Result<Arc<dyn AbiItemProvider + Send + Sync>> {
-    let key = str::from_utf8(key)?;
+const PREFETCH_CONCURRENCY: usize = 32;
+const PAR_MIN_ROWS: usize = 64;
 
-    cache.get_or_insert_async(
-        key,
-        async { AbiItemProviderFactory::create(key).await }
-    ).await
+// The ABI item providers of a block, by decoder and, for the decoders depending on the row, by context.
+pub struct BlockAbiItemProviders<'a> {
+    scopes: HashMap<&'a [u8], ContextScope>,
+    m: HashMap<(&'a [u8], AbiContext), SharedAbiItemProvider>
+}
+
+impl<'a> BlockAbiItemProviders<'a> {
+    pub fn get(&self, key: &'a [u8], ctx: &AbiContext) -> Option<&SharedAbiItemProvider> {
+        let scope = self.scopes.get(key)?;
+        self.m.get(&(key, scope.apply(ctx)))
+    }
+}
+
+fn context_scope(key: &[u8]) -> ContextScope {
+    str::from_utf8(key).map_or(ContextScope::None, AbiItemProviderFactory::context_scope)
+}
+
+// Creates the providers of all the distinct decoders of a block concurrently, so that rows can then be decoded
+// without awaiting. Decoders whose provider cannot be created are left out.
+pub async fn prefetch_abi_item_providers<'a>(
+    cache: &AbiItemProviderCache,
+    abis_col: &'a ListArray,
+    ctx_cols: &AbiContextColumns
+) -> BlockAbiItemProviders<'a> {
+    prefetch_abi_item_providers_with(cache, abis_col, |i| vec![ctx_cols.get(i)]).await
+}
+
+// Same as `prefetch_abi_item_providers`, for rows decoded in several contexts, e.g. the frames of a trace
+// calling different contracts.
+pub async fn prefetch_abi_item_providers_with<'a, F>(
+    cache: &AbiItemProviderCache,
+    abis_col: &'a ListArray,
+    row_contexts: F
+) -> BlockAbiItemProviders<'a>
+where
+    F: Fn(usize) -> Vec<AbiContext>
+{
+    let abis = abis_col.values().as_binary::<i32>();
+    let mut scopes = HashMap::new();
+    let mut keys = HashSet::new();
+
+    for (i, w) in abis_col.value_offsets().windows(2).enumerate() {
+        let ctxs = row_contexts(i);
+
+        for j in w[0] as usize..w[1] as usize {
+            if abis.is_valid(j) {
+                let key = abis.value(j);
+                let scope = scopes.entry(key).or_insert_with(|| context_scope(key));
+                keys.extend(ctxs.iter().map(|ctx| (key, scope.apply(ctx))));
+            }
+        }
+    }
+
+    let m = iter(keys)
+        .map(async |(key, ctx)| {
+            let p = get_cached_abi_item_provider(cache, key, &ctx).await;
+            ((key, ctx), p)
+        })
+        .buffer_unordered(PREFETCH_CONCURRENCY)
+        .filter_map(async |((key, ctx), p)| match p {
+            Ok(p) => Some(((key, ctx), p)),
+            Err(err) => {
+                debug!(decoder = %String::from_utf8_lossy(key), context = %ctx, error = %err, "ABI item provider not available");
+                None
+            }
+        })
+        .collect()
+        .await;
+
+    BlockAbiItemProviders { scopes, m }
+}
+
+// The optional columns giving the context of the rows to the decoders depending on it: the address of the
+// contract, as 20 bytes or as a hex string, and the block number.
+#[derive(Default)]
+pub struct AbiContextColumns {
+    address: Option<BinaryArray>,
+    block_number: Option<Int64Array>
+}
+
+impl AbiContextColumns {
+    pub fn new(input_batch: &RecordBatch) -> Result<Self> {
+        Ok(Self {
+            address: input_batch.get_optional_binary_column("address")?,
+            block_number: input_batch.get_optional_int64_column("block_number")?
+        })
+    }
+
+    pub fn get(&self, i: usize) -> AbiContext {
+        AbiContext {
+            address: row_address(self.address.as_ref(), i),
+            block_number: self.block_number.as_ref()
+                .filter(|col| col.is_valid(i))
+                .and_then(|col| u64::try_from(col.value(i)).ok())
+        }
+    }
+}
+
+// Contract address of a row, given either as 20 bytes or as a hex string.
+fn row_address(address_col: Option<&BinaryArray>, i: usize) -> Option<Address> {
+    let address_col = address_col?;
+
+    if address_col.is_null(i) {
+        return None;
+    }
+
+    match address_col.value(i) {
+        b if b.len() == 20 => Some(Address::from_slice(b)),
+        b => str::from_utf8(b).ok()?.parse().inspect_err(|err| debug!(row = i, error = %err, "invalid address")).ok()
+    }
+}
+
+// Splits the rows of a block into chunks processed on the rayon thread pool, the results of the chunks are
+// then concatenated in row order.
+pub fn par_process_chunks<F>(num_rows: usize, f: F) -> Result<ArrayRef>
+where
+    F: Fn(Range<usize>) -> Result<ArrayRef> + Send + Sync
+{
+    let chunk_size = num_rows.div_ceil(current_num_threads() * 4).max(PAR_MIN_ROWS);
+
+    let chunks = block_in_place(|| {
+        (0..num_rows.div_ceil(chunk_size))
+            .into_par_iter()
+            .map(|c| f(c * chunk_size..((c + 1) * chunk_size).min(num_rows)))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    match chunks.len() {
+        1 => Ok(chunks[0].clone()),
+        _ => Ok(concat(&chunks.iter().map(|c| c.as_ref()).collect::<Vec<_>>())?)
+    }
 }
 
 pub fn get_cached_func_sync(
@@ -76,3 +272,224 @@ pub fn get_cached_func_sync(
     )
 }
 
+
+// Arguments of a command with all their default values.
+#[cfg(test)]
+pub fn default_args<T: clap::Args + clap::FromArgMatches>() -> T {
+    let matches = T::augment_args(clap::Command::new("test")).get_matches_from(["test"]);
+    T::from_arg_matches(&matches).unwrap()
+}
+
+#[cfg(test)]
+pub fn binary_list_array(rows: Vec<Option<Vec<Option<&[u8]>>>>) -> ListArray {
+    let mut builder = arrow::array::ListBuilder::new(BinaryBuilder::new());
+    for row in rows {
+        match row {
+            Some(values) => {
+                values.iter().for_each(|v| builder.values().append_option(*v));
+                builder.append(true);
+            },
+            None => builder.append(false)
+        }
+    }
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{DictionaryArray, FixedSizeBinaryArray, Int32Array, LargeBinaryArray, LargeStringArray, StringArray, StringViewArray, UInt64Array};
+    use arrow::compute::cast;
+    use arrow::datatypes::Int32Type;
+    use alloy::primitives::{address, hex, B256};
+    use serde_json::json;
+    use crate::cli::serve_mock::{MockFixture, MockServer};
+    use super::*;
+
+    fn batch(col: ArrayRef) -> RecordBatch {
+        RecordBatch::try_from_iter([("col", col)]).unwrap()
+    }
+
+    fn binary_values(col: &BinaryArray) -> Vec<Option<&[u8]>> {
+        col.iter().collect()
+    }
+
+    #[test]
+    fn reads_binary_columns() {
+        let expected = [Some(b"ab".as_slice()), None, Some(b"cd")];
+        let cols: Vec<ArrayRef> = vec![
+            Arc::new(BinaryArray::from(vec![Some(b"ab".as_slice()), None, Some(b"cd")])),
+            Arc::new(LargeBinaryArray::from(vec![Some(b"ab".as_slice()), None, Some(b"cd")])),
+            Arc::new(StringArray::from(vec![Some("ab"), None, Some("cd")])),
+            Arc::new(LargeStringArray::from(vec![Some("ab"), None, Some("cd")])),
+            Arc::new(StringViewArray::from(vec![Some("ab"), None, Some("cd")])),
+            Arc::new(FixedSizeBinaryArray::try_from_sparse_iter_with_size(expected.into_iter(), 2).unwrap()),
+            Arc::new(vec![Some("ab"), None, Some("cd")].into_iter().collect::<DictionaryArray<Int32Type>>())
+        ];
+
+        for col in cols {
+            let data_type = col.data_type().clone();
+            let res = batch(col).get_binary_column("col").unwrap();
+            assert_eq!(binary_values(&res), expected, "{data_type}");
+        }
+    }
+
+    #[test]
+    fn reads_binary_list_columns() {
+        let list = binary_list_array(vec![Some(vec![Some(b"ab"), None]), None, Some(vec![])]);
+        let large_list = cast(&list, &DataType::new_large_list(DataType::Binary, true)).unwrap();
+        let string_list = cast(&list, &DataType::new_list(DataType::Utf8, true)).unwrap();
+
+        for col in [Arc::new(list) as ArrayRef, large_list, string_list] {
+            let data_type = col.data_type().clone();
+            let res = batch(col).get_binary_list_column("col").unwrap();
+
+            assert_eq!(res.len(), 3, "{data_type}");
+            assert_eq!(binary_values(res.value(0).as_binary::<i32>()), [Some(b"ab".as_slice()), None], "{data_type}");
+            assert!(res.is_null(1), "{data_type}");
+            assert_eq!(res.value(2).len(), 0, "{data_type}");
+        }
+    }
+
+    #[test]
+    fn reads_int64_columns() {
+        let res = batch(Arc::new(Int32Array::from(vec![Some(1), None]))).get_int64_column("col").unwrap();
+        assert_eq!(res.iter().collect::<Vec<_>>(), [Some(1), None]);
+
+        let res = batch(Arc::new(UInt64Array::from(vec![42]))).get_int64_column("col").unwrap();
+        assert_eq!(res.value(0), 42);
+
+        // values are not silently truncated to NULL
+        let err = batch(Arc::new(UInt64Array::from(vec![u64::MAX]))).get_int64_column("col").unwrap_err();
+        assert_eq!(err.to_string(), "cannot read column col as Int64");
+    }
+
+    #[test]
+    fn rejects_missing_or_unsupported_columns() {
+        let b = batch(Arc::new(BinaryArray::from(vec![b"ab".as_slice()])));
+
+        assert_eq!(b.get_binary_column("other").unwrap_err().to_string(), "cannot find column other");
+        assert!(b.get_optional_binary_column("other").unwrap().is_none());
+        assert!(b.get_optional_int64_column("other").unwrap().is_none());
+        assert!(b.get_optional_binary_column("col").unwrap().is_some());
+
+        let err = b.get_int64_column("col").unwrap_err();
+        assert_eq!(err.to_string(), "column col has type Binary and cannot be read as Int64");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn processes_chunks_in_row_order() {
+        let num_rows = PAR_MIN_ROWS * current_num_threads() * 8 + 1;
+        let rows = |rows: Range<usize>| Ok(Arc::new(Int64Array::from_iter_values(rows.map(|i| i as i64))) as ArrayRef);
+
+        for n in [1, PAR_MIN_ROWS, num_rows] {
+            let res = par_process_chunks(n, rows).unwrap();
+            assert_eq!(res.as_primitive::<Int64Type>().values().to_vec(), (0..n as i64).collect::<Vec<_>>());
+        }
+
+        let err = par_process_chunks(num_rows, |r| match r.contains(&(num_rows - 1)) {
+            true => bail!("cannot process row {}", num_rows - 1),
+            false => rows(r)
+        }).unwrap_err();
+        assert_eq!(err.to_string(), format!("cannot process row {}", num_rows - 1));
+    }
+
+    #[tokio::test]
+    async fn prefetches_the_providers_of_a_block() {
+        let transfer = b"event Transfer(address indexed from, address indexed to, uint256 value)".as_slice();
+        let approval = b"event Approval(address indexed owner, address indexed spender, uint256 value)".as_slice();
+        let missing = b"file:///nonexistent/erc20.json".as_slice();
+        let abis_col = binary_list_array(vec![
+            Some(vec![Some(transfer), None, Some(missing)]),
+            None,
+            Some(vec![Some(transfer), Some(approval)])
+        ]);
+
+        let cache = default_args::<AbiCacheArgs>().build().unwrap();
+        let providers = prefetch_abi_item_providers(&cache, &abis_col, &AbiContextColumns::default()).await;
+        let ctx = AbiContext::default();
+
+        assert!(providers.get(transfer, &ctx).is_some());
+        assert!(providers.get(approval, &ctx).is_some());
+        assert!(providers.get(missing, &ctx).is_none());
+        assert!(providers.get(b"event Deposit(address indexed dst, uint256 wad)", &ctx).is_none());
+        assert_eq!(providers.m.len(), 2);
+    }
+
+    #[test]
+    fn reads_row_contexts() {
+        let address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let block_numbers: ArrayRef = Arc::new(Int64Array::from(vec![Some(1), Some(-1), None, Some(2)]));
+        let contexts = |addresses: ArrayRef| -> Vec<AbiContext> {
+            let batch = RecordBatch::try_from_iter([("address", addresses), ("block_number", block_numbers.clone())]).unwrap();
+            let ctx_cols = AbiContextColumns::new(&batch).unwrap();
+            (0..batch.num_rows()).map(|i| ctx_cols.get(i)).collect()
+        };
+        let ctx = |address, block_number| AbiContext { address, block_number };
+
+        let bytes: ArrayRef = Arc::new(BinaryArray::from(vec![
+            Some(address.as_slice()), Some(address.as_slice()), None, Some(&address[1..])
+        ]));
+        assert_eq!(contexts(bytes), [ctx(Some(address), Some(1)), ctx(Some(address), None), ctx(None, None), ctx(None, Some(2))]);
+
+        let strings: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"), Some("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), None, Some("0xa0b8")
+        ]));
+        assert_eq!(contexts(strings), [ctx(Some(address), Some(1)), ctx(Some(address), None), ctx(None, None), ctx(None, Some(2))]);
+
+        assert_eq!(AbiContextColumns::default().get(0), AbiContext::default());
+    }
+
+    #[tokio::test]
+    async fn resolves_proxies_at_the_block_of_the_row() {
+        const PROXY: Address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        const V1: Address = address!("43506849d7c04f9138d1a2050bbf3a0c054402dd");
+        const V2: Address = address!("bebc44782c7db0a1a60cb6fe97d0b483032ff1c7");
+        const IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+        let transfer = hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+        let approval = hex!("8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925");
+
+        // A Sourcify repository with both implementations.
+        let root = std::env::temp_dir().join(format!("ch_evm_utils_{}_proxy", std::process::id()));
+        for (address, event) in [
+            (V1, "event Transfer(address indexed from, address indexed to, uint256 value)"),
+            (V2, "event Approval(address indexed owner, address indexed spender, uint256 value)")
+        ] {
+            let dir = root.join(format!("contracts/full_match/1/{}", address.to_checksum(None)));
+            let abi = serde_json::to_value(alloy::json_abi::JsonAbi::parse([event]).unwrap()).unwrap();
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("metadata.json"), json!({"output": {"abi": abi}}).to_string()).unwrap();
+        }
+
+        // The proxy is upgraded from V1 to V2 at block 110.
+        let word = |address: Address| B256::left_padding_from(address.as_slice());
+        let fixture = json!({"responses": [
+            {"method": "eth_getStorageAt", "params": [PROXY, IMPLEMENTATION_SLOT, "0x64"], "result": word(V1)},
+            {"method": "eth_getStorageAt", "params": [PROXY, IMPLEMENTATION_SLOT, "0x6e"], "result": word(V2)},
+            {"method": "eth_getStorageAt", "result": B256::ZERO},
+            {"method": "eth_getCode", "result": "0x"}
+        ]});
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc = format!("http://{}", listener.local_addr().unwrap());
+        let server = MockServer::new(serde_yaml::from_str::<MockFixture>(&fixture.to_string()).unwrap());
+        tokio::spawn(Arc::new(server).serve_on(listener));
+
+        let decoder = format!("proxy://?rpc={rpc}&source=sourcify://{}%23chain%3D1", root.display());
+        let abis_col = binary_list_array(vec![Some(vec![Some(decoder.as_bytes())]); 3]);
+        let batch = RecordBatch::try_from_iter([
+            ("address", Arc::new(BinaryArray::from(vec![PROXY.as_slice(); 3])) as ArrayRef),
+            ("block_number", Arc::new(Int64Array::from(vec![100, 110, 110])) as ArrayRef)
+        ]).unwrap();
+        let ctx_cols = AbiContextColumns::new(&batch).unwrap();
+
+        let cache = default_args::<AbiCacheArgs>().build().unwrap();
+        let providers = prefetch_abi_item_providers(&cache, &abis_col, &ctx_cols).await;
+        let provider = |i| providers.get(decoder.as_bytes(), &ctx_cols.get(i)).unwrap();
+
+        assert!(provider(0).get_events(&transfer).is_ok());
+        assert!(provider(0).get_events(&approval).is_err());
+        assert!(provider(1).get_events(&approval).is_ok());
+        assert!(provider(1).get_events(&transfer).is_err());
+        assert_eq!(providers.m.len(), 2);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
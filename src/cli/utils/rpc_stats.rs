@@ -0,0 +1,56 @@
+use std::time::Duration;
+use clap::Args;
+use tokio::time::sleep;
+use tracing::{info, warn};
+use crate::evm::rpc::RPC_METRICS;
+
+// Target of the stats lines, always enabled at the info level so that `--stats` prints them whatever the log level.
+pub const STATS_TARGET: &str = "rpc_stats";
+
+#[derive(Debug, Clone, Args)]
+pub struct RpcStatsArgs {
+    #[arg(long, default_value_t = false)]
+    stats: bool,
+
+    #[arg(long, value_parser = parse_duration)]
+    stats_interval: Option<Duration>,
+
+    #[arg(long)]
+    stats_textfile: Option<String>
+}
+
+impl RpcStatsArgs {
+    pub fn start_reporter(&self) {
+        if let Some(interval) = self.stats_interval {
+            let textfile = self.stats_textfile.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    sleep(interval).await;
+                    emit_rpc_stats(true, textfile.as_deref());
+                }
+            });
+        }
+    }
+
+    pub fn on_block_end(&self) {
+        if self.stats_interval.is_none() {
+            emit_rpc_stats(self.stats, self.stats_textfile.as_deref());
+        }
+    }
+}
+
+fn emit_rpc_stats(log: bool, textfile: Option<&str>) {
+    if log {
+        info!(target: STATS_TARGET, "{}", RPC_METRICS.to_logfmt());
+    }
+
+    if let Some(path) = textfile
+        && let Err(err) = RPC_METRICS.write_prometheus_textfile(path) {
+        warn!(path, error = %err, "cannot write rpc stats textfile");
+    }
+}
+
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    duration_str::parse(s)
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Context, Result};
+use alloy::json_abi::JsonAbi;
+use futures::TryStreamExt;
+use object_store::ObjectStore;
+use object_store::path::{Path, PathPart};
+use serde_json::Value;
+
+// Parses a document holding one or several ABIs, named after `name` and, for bundles, their key in the bundle:
+// - a JSON ABI, i.e. an array of ABI items, or a single ABI item,
+// - a Solidity human-readable ABI, i.e. an array of signatures,
+// - a Hardhat or Foundry artifact, i.e. an object with an `abi` field,
+// - a bundle, i.e. an object mapping names to any of the above.
+pub fn parse_abi_bundle(name: &str, content: &[u8]) -> Result<Vec<(String, JsonAbi)>> {
+    let value: Value = serde_json::from_slice(content).with_context(|| format!("cannot parse ABI document {name}"))?;
+
+    match value {
+        Value::Object(mut m) if !m.contains_key("abi") && !m.contains_key("type") => m
+            .iter_mut()
+            .map(|(key, v)| Ok((format!("{name}#{key}"), parse_abi(v.take()).with_context(|| format!("cannot parse ABI {key} of {name}"))?)))
+            .collect(),
+        v => Ok(vec![(name.to_string(), parse_abi(v).with_context(|| format!("cannot parse ABI document {name}"))?)])
+    }
+}
+
+fn parse_abi(value: Value) -> Result<JsonAbi> {
+    match value {
+        Value::Object(mut m) => match m.remove("abi") {
+            Some(abi) => parse_abi(abi),
+            None if m.contains_key("type") => parse_abi(Value::Array(vec![Value::Object(m)])),
+            None => bail!("object is neither an ABI, an ABI item nor an artifact")
+        },
+        Value::Array(items) if items.iter().all(Value::is_string) => {
+            Ok(JsonAbi::parse(items.iter().filter_map(Value::as_str))?)
+        },
+        v @ Value::Array(_) => Ok(serde_json::from_value(v)?),
+        _ => bail!("ABI must be an array or an object")
+    }
+}
+
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+// Splits a URL path into the directory before its first wildcard and the glob pattern relative to it.
+pub fn split_glob(path: &str) -> (&str, &str) {
+    let wildcard = path.find(['*', '?']).unwrap_or(path.len());
+    let dir = path[..wildcard].rfind('/').map_or(0, |i| i + 1);
+    path.split_at(dir)
+}
+
+// Lists the objects below `dir` matching a glob pattern, where `*` matches any part of a path segment, `?` any
+// single character of a segment and `**` any number of segments, e.g. `out/**/*.json` for the artifacts of a
+// Foundry project. The objects are sorted by path.
+pub async fn list_glob(objstr: &dyn ObjectStore, dir: &Path, pattern: &str) -> Result<Vec<Path>> {
+    let pattern: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty()).collect();
+    let depth = dir.parts().count();
+
+    let mut paths: Vec<Path> = objstr.list(Some(dir))
+        .try_filter(|meta| {
+            let parts: Vec<PathPart> = meta.location.parts().skip(depth).collect();
+            let parts: Vec<&str> = parts.iter().map(|p| p.as_ref()).collect();
+            futures::future::ready(match_segments(&pattern, &parts))
+        })
+        .map_ok(|meta| meta.location)
+        .try_collect()
+        .await?;
+
+    paths.sort();
+    Ok(paths)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+        Some((p, rest)) => match path.split_first() {
+            Some((s, path)) => match_segment(p.as_bytes(), s.as_bytes()) && match_segments(rest, path),
+            None => false
+        }
+    }
+}
+
+fn match_segment(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| match_segment(rest, &s[i..])),
+        Some((b'?', rest)) => s.split_first().is_some_and(|(_, s)| match_segment(rest, s)),
+        Some((c, rest)) => s.split_first().is_some_and(|(d, s)| c == d && match_segment(rest, s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        let pattern: Vec<&str> = pattern.split('/').collect();
+        let path: Vec<&str> = path.split('/').collect();
+        match_segments(&pattern, &path)
+    }
+
+    #[test]
+    fn matches_globs() {
+        let cases = [
+            ("erc20.json", "erc20.json", true),
+            ("erc20.json", "erc721.json", false),
+            ("*.json", "erc20.json", true),
+            ("*.json", "erc20.abi", false),
+            ("*.json", "tokens/erc20.json", false),
+            ("erc*.json", "erc.json", true),
+            ("*20*", "erc20.json", true),
+            ("erc??.json", "erc20.json", true),
+            ("erc??.json", "erc721.json", false),
+            ("erc?.json", "erc.json", false),
+            ("**/*.json", "erc20.json", true),
+            ("**/*.json", "out/ERC20.sol/ERC20.json", true),
+            ("out/**/*.json", "out/ERC20.sol/ERC20.json", true),
+            ("out/**/*.json", "src/ERC20.sol/ERC20.json", false),
+            ("out/**", "out/ERC20.sol/ERC20.json", true),
+            ("**/ERC20.sol/*.json", "out/ERC20.sol/ERC20.json", true),
+            ("**/ERC20.sol/*.json", "out/ERC721.sol/ERC721.json", false),
+            ("*/*.json", "out/ERC20.sol/ERC20.json", false)
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(matches(pattern, path), expected, "{pattern} against {path}");
+        }
+    }
+
+    #[test]
+    fn splits_globs() {
+        assert_eq!(split_glob("/project/out/**/*.json"), ("/project/out/", "**/*.json"));
+        assert_eq!(split_glob("/abis/erc??.json"), ("/abis/", "erc??.json"));
+        assert_eq!(split_glob("/abis/"), ("/abis/", ""));
+        assert!(is_glob("/abis/erc?.json"));
+        assert!(!is_glob("/abis/erc20.json"));
+    }
+
+    fn names(abis: &[(String, JsonAbi)]) -> Vec<&str> {
+        abis.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_abi_shapes() {
+        let json_abi = r#"[{"type":"event","name":"Transfer","anonymous":false,"inputs":[
+            {"name":"from","type":"address","indexed":true},
+            {"name":"to","type":"address","indexed":true},
+            {"name":"value","type":"uint256","indexed":false}]}]"#;
+        let human_readable = r#"["event Transfer(address indexed from, address indexed to, uint256 value)"]"#;
+        let item = r#"{"type":"function","name":"transfer","stateMutability":"nonpayable",
+            "inputs":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],
+            "outputs":[{"name":"","type":"bool"}]}"#;
+        let artifact = format!(r#"{{"abi": {json_abi}, "bytecode": "0x"}}"#);
+
+        for content in [json_abi, human_readable, item, &artifact] {
+            let abis = parse_abi_bundle("erc20.json", content.as_bytes()).unwrap();
+
+            assert_eq!(names(&abis), ["erc20.json"]);
+            assert_eq!(abis[0].1.len(), 1, "{content}");
+        }
+    }
+
+    #[test]
+    fn parses_bundles() {
+        let bundle = r#"{
+            "ERC20": ["event Transfer(address indexed from, address indexed to, uint256 value)"],
+            "ERC721": {"abi": ["event Transfer(address indexed from, address indexed to, uint256 indexed id)"]}
+        }"#;
+        let abis = parse_abi_bundle("tokens.json", bundle.as_bytes()).unwrap();
+
+        assert_eq!(names(&abis), ["tokens.json#ERC20", "tokens.json#ERC721"]);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        let cases = [
+            "not json",
+            "42",
+            r#"{"ERC20": 42}"#,
+            r#"{"ERC20": {"bytecode": "0x"}}"#,
+            r#"["not a signature("]"#
+        ];
+
+        for content in cases {
+            assert!(parse_abi_bundle("doc.json", content.as_bytes()).is_err(), "{content}");
+        }
+    }
+}
@@ -1,22 +1,41 @@
 use anyhow::{Result,bail};
-use super::event::Event;
+use super::event::{normalize_anonymous, Event};
 use super::function::Function;
+use super::constructor::Constructor;
 
 #[derive(Debug, Clone)]
 
 pub enum AbiItem {
     Event(Event),
-    Function(Function)
+    Function(Function),
+    Constructor(Constructor)
 }
 
 impl AbiItem {
     pub fn parse(s: &str) -> Result<AbiItem> {
-        let item = alloy::json_abi::AbiItem::parse(s)?;
+        let item = alloy::json_abi::AbiItem::parse(&normalize_anonymous(s))?;
         
         match item {
             alloy::json_abi::AbiItem::Function(f) => Ok(AbiItem::Function(Function::new(f.into_owned())?)),
             alloy::json_abi::AbiItem::Event(e) => Ok(AbiItem::Event(Event::new(e.into_owned())?)),
-            _ => bail!("ABI item is neither function nor event")
+            alloy::json_abi::AbiItem::Constructor(c) => Ok(AbiItem::Constructor(Constructor::new(c.into_owned())?)),
+            _ => bail!("ABI item is neither function, event nor constructor")
         }
     }
-}
\ No newline at end of file
+
+    pub fn selector(&self) -> Vec<u8> {
+        match self {
+            AbiItem::Event(evt) => evt.selector().to_vec(),
+            AbiItem::Function(func) => func.selector().to_vec(),
+            AbiItem::Constructor(_) => Vec::new()
+        }
+    }
+
+    pub fn fullsig(&self) -> &str {
+        match self {
+            AbiItem::Event(evt) => evt.fullsig(),
+            AbiItem::Function(func) => func.fullsig(),
+            AbiItem::Constructor(ctor) => ctor.fullsig()
+        }
+    }
+}
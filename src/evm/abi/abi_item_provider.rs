@@ -1,25 +1,119 @@
+use std::fmt::{self, Display, Formatter};
 use std::sync::Arc;
 use anyhow::{Result,bail};
+use alloy::primitives::Address;
 pub use super::abi_item::AbiItem;
 pub use super::event::Event;
 pub use super::function::Function;
+pub use super::constructor::Constructor;
 use super::file_abi_item_provider::FileAbiItemProvider;
+use super::object_store_url::is_cloud_url;
+use super::document_cache::DocumentCache;
 use super::fullsig_abi_item_provider::FullsigAbiItemProvider;
+use super::sourcify_abi_item_provider::SourcifyAbiItemProvider;
+use super::sigdb_abi_item_provider::SigdbAbiItemProvider;
+use super::proxy_abi_item_provider::ProxyAbiItemProvider;
+use super::etherscan_abi_item_provider::EtherscanAbiItemProvider;
 
 pub trait AbiItemProvider {
     fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem>;
 
-    fn get_event(&self, selector: &[u8]) -> Result<&Event> {
-        match self.get_abi_item(selector)? {
-            AbiItem::Event(evt) => Ok(evt),
-            _ => bail!("abi item is not an event")
+    // All the candidate items for a selector, for providers mapping a selector to several items.
+    fn get_abi_items(&self, selector: &[u8]) -> Result<Vec<&AbiItem>> {
+        Ok(vec![self.get_abi_item(selector)?])
+    }
+
+    // Whether the values decoded with the items of this provider must re-encode to the input, for providers
+    // whose candidates are not known to match the contract, e.g. signature databases.
+    fn is_strict(&self) -> bool {
+        false
+    }
+
+    // Whether the event signatures of this provider may lack the `indexed` markers, e.g. the ones of signature
+    // databases, in which case the layouts of indexed parameters fitting the log are tried.
+    fn has_unmarked_events(&self) -> bool {
+        false
+    }
+
+    // The anonymous events, which have no selector and are tried on any log with as many topics as they have.
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        Vec::new()
+    }
+
+    // The candidate constructors, which have no selector either.
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        bail!("abi has no constructor")
+    }
+
+    fn get_events(&self, selector: &[u8]) -> Result<Vec<&Event>> {
+        let events: Vec<&Event> = self.get_abi_items(selector)?
+            .into_iter()
+            .filter_map(|item| match item {
+                AbiItem::Event(evt) => Some(evt),
+                _ => None
+            })
+            .collect();
+
+        if events.is_empty() {
+            bail!("abi item is not an event");
         }
+
+        Ok(events)
     }
 
-    fn get_function(&self, selector: &[u8]) -> Result<&Function> {
-        match self.get_abi_item(selector)? {
-            AbiItem::Function(func) => Ok(func),
-            _ => bail!("abi item is not an event")
+    fn get_functions(&self, selector: &[u8]) -> Result<Vec<&Function>> {
+        let funcs: Vec<&Function> = self.get_abi_items(selector)?
+            .into_iter()
+            .filter_map(|item| match item {
+                AbiItem::Function(func) => Some(func),
+                _ => None
+            })
+            .collect();
+
+        if funcs.is_empty() {
+            bail!("abi item is not a function");
+        }
+
+        Ok(funcs)
+    }
+}
+
+// Information about the row being decoded, used by the providers looking up the ABI of a given contract.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AbiContext {
+    pub address: Option<Address>,
+    pub block_number: Option<u64>
+}
+
+impl Display for AbiContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(address) = self.address {
+            write!(f, "{}", address)?;
+        }
+
+        if let Some(block_number) = self.block_number {
+            write!(f, "#{}", block_number)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The part of the context of a row the provider created for a decoder depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextScope {
+    None,
+    Address,
+    // The address and the block number, for the providers whose ABI may change from one block to the next.
+    AddressBlock
+}
+
+impl ContextScope {
+    pub fn apply(&self, ctx: &AbiContext) -> AbiContext {
+        match *self {
+            ContextScope::None => AbiContext::default(),
+            ContextScope::Address => AbiContext { address: ctx.address, block_number: None },
+            ContextScope::AddressBlock => ctx.clone()
         }
     }
 }
@@ -27,23 +121,114 @@ pub trait AbiItemProvider {
 pub struct AbiItemProviderFactory;
 
 impl AbiItemProviderFactory {
-    pub async fn create(s: &str) -> Result<Arc<dyn AbiItemProvider + Sync + Send>> {
+    // The context the provider created for a decoder depends on, a provider being created for each distinct
+    // context.
+    pub fn context_scope(s: &str) -> ContextScope {
+        match s {
+            _ if s.starts_with("sourcify://") => ContextScope::Address,
+            _ if s.starts_with("proxy://") => ContextScope::AddressBlock,
+            _ if s.starts_with("etherscan://") => EtherscanAbiItemProvider::context_scope(s),
+            _ => ContextScope::None
+        }
+    }
+
+    pub async fn create(s: &str, ctx: &AbiContext, docs: &DocumentCache) -> Result<Arc<dyn AbiItemProvider + Sync + Send>> {
         match s {
-            _ if s.starts_with("http://") => FileAbiItemProvider::new(s)
+            _ if s.starts_with("sigdb://") => SigdbAbiItemProvider::new(s)
                 .await
                 .map(Arc::new)
                 .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
-            _ if s.starts_with("https://") => FileAbiItemProvider::new(s)
+            _ if s.starts_with("proxy://") => Box::pin(ProxyAbiItemProvider::new(s, ctx, docs))
                 .await
                 .map(Arc::new)
                 .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
-            _ if s.starts_with("file://") => FileAbiItemProvider::new(s)
+            _ if s.starts_with("etherscan://") => EtherscanAbiItemProvider::new(s, ctx, docs)
                 .await
                 .map(Arc::new)
                 .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
+            _ if s.starts_with("sourcify://") => SourcifyAbiItemProvider::new(s, ctx.address)
+                .await
+                .map(Arc::new)
+                .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
+            _ if s.starts_with("http://") => FileAbiItemProvider::new(s, docs)
+                .await
+                .map(Arc::new)
+                .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
+            _ if s.starts_with("https://") => FileAbiItemProvider::new(s, docs)
+                .await
+                .map(Arc::new)
+                .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
+            _ if s.starts_with("file://") => FileAbiItemProvider::new(s, &DocumentCache::default())
+                .await
+                .map(Arc::new)
+                .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
+            _ if is_cloud_url(s) => FileAbiItemProvider::new(s, docs)
+                .await
+                .map(Arc::new)
+                .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
+            _ if is_inline_abi(s) => FileAbiItemProvider::from_inline(s)
+                .map(Arc::new)
+                .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send> ),
             _ => FullsigAbiItemProvider::new(s)
                 .map(Arc::new)
                 .map(|x| x as Arc<dyn AbiItemProvider + Sync + Send>)
         }
     }
-}
\ No newline at end of file
+}
+
+// Whether a decoder is a JSON ABI document given inline, rather than a URL or a fullsig.
+fn is_inline_abi(s: &str) -> bool {
+    s.trim_start().starts_with(['[', '{'])
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+    use serde_json::{json, Value};
+    use crate::evm::abi::decoding::{decode_call, decode_event};
+    use super::*;
+
+    const INITIALIZE: &str = r#"{"type":"function","name":"initialize","stateMutability":"nonpayable","inputs":[{"name":"key","type":"tuple","internalType":"struct PoolKey","components":[{"name":"currency0","type":"address"},{"name":"currency1","type":"address"},{"name":"fee","type":"uint24"},{"name":"tickSpacing","type":"int24"},{"name":"hooks","type":"address"}]},{"name":"sqrtPriceX96","type":"uint160"}],"outputs":[{"name":"tick","type":"int24"}]}"#;
+
+    async fn create(s: &str) -> Result<Arc<dyn AbiItemProvider + Sync + Send>> {
+        AbiItemProviderFactory::create(s, &AbiContext::default(), &DocumentCache::default()).await
+    }
+
+    #[tokio::test]
+    async fn decodes_with_inline_abis() {
+        let p = create(&format!("  {INITIALIZE}")).await.unwrap();
+        let func = Function::new(serde_json::from_str(INITIALIZE).unwrap()).unwrap();
+        let key = json!({
+            "currency0": "0x0000000000000000000000000000000000000000", "currency1": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "fee": 3000, "tickSpacing": -60, "hooks": "0x0000000000000000000000000000000000000000"
+        });
+        let input = func.abi_encode_input(&func.coerce_inputs(&[key, json!("79228162514264337593543950336")]).unwrap()).unwrap();
+
+        let decoded: Value = serde_json::from_slice(&decode_call(p.as_ref(), &input, None).unwrap()).unwrap();
+        assert_eq!(decoded["value"]["signature"], "initialize((address,address,uint24,int24,address),uint160)");
+        // the components of the struct keep their names
+        assert_eq!(decoded["value"]["inputs"]["arg0"]["fee"], 3000);
+        assert_eq!(decoded["value"]["inputs"]["arg0"]["tickSpacing"], -60);
+
+        let p = create(r#"[{"type":"event","name":"Deposit","anonymous":false,"inputs":[{"name":"dst","type":"address","indexed":true},{"name":"wad","type":"uint256","indexed":false}]}]"#).await.unwrap();
+        let topics = [
+            hex!("e1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c").into(),
+            hex!("000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").into()
+        ];
+        let data = hex!("0000000000000000000000000000000000000000000000000de0b6b3a7640000");
+
+        let decoded: Value = serde_json::from_slice(&decode_event(p.as_ref(), &topics, &data).unwrap()).unwrap();
+        assert_eq!(decoded["value"]["signature"], "Deposit(address,uint256)");
+        assert_eq!(decoded["value"]["inputs"]["arg1"], "1000000000000000000");
+    }
+
+    #[tokio::test]
+    async fn tells_inline_abis_from_fullsigs() {
+        assert!(is_inline_abi(r#"[]"#));
+        assert!(is_inline_abi(" \n{\"abi\": []}"));
+        assert!(!is_inline_abi("event Deposit(address indexed dst, uint256 wad)"));
+
+        assert!(create("event Deposit(address indexed dst, uint256 wad)").await.is_ok());
+        assert!(create(r#"[{"type":"event","name":"Deposit""#).await.is_err());
+    }
+}
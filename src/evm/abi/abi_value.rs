@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use alloy::dyn_abi::{DynSolType, DynSolValue};
+use alloy::primitives::U256;
+use serde_json::Value;
+use super::decode_guard::guard_decoding;
+
+// Parses a Solidity type, e.g. `uint256`, `(address,uint256[])` or `tuple(address,uint256[])`.
+pub fn parse_type(s: &str) -> Result<DynSolType> {
+    DynSolType::parse(s.trim()).with_context(|| format!("cannot parse type {s}"))
+}
+
+// Decodes ABI-encoded data as Solidity's `abi.decode(data, (...))`, i.e. the members of a tuple type are decoded
+// as a sequence of parameters.
+pub fn abi_decode(ty: &DynSolType, data: &[u8]) -> Result<DynSolValue> {
+    Ok(guard_decoding(|| ty.abi_decode_params(data))?)
+}
+
+// Whether the decoded value fits its type, as the decoder keeps the whole word of the values narrower than 32 bytes,
+// e.g. an `uint8` may hold 1000 and a `bytes16` non-zero padding, which encode back to the same word.
+pub fn is_in_range(value: &DynSolValue) -> bool {
+    match value {
+        DynSolValue::Uint(v, bits) => v.bit_len() <= *bits,
+        DynSolValue::Int(v, bits) => {
+            let raw = v.into_raw();
+            let magnitude = if v.is_negative() { !raw } else { raw };
+            magnitude >> (bits - 1) == U256::ZERO
+        },
+        DynSolValue::FixedBytes(word, size) => word[*size..].iter().all(|b| *b == 0),
+        DynSolValue::Tuple(values) | DynSolValue::Array(values) | DynSolValue::FixedArray(values) =>
+            values.iter().all(is_in_range),
+        _ => true
+    }
+}
+
+// Encodes a JSON value as Solidity's `abi.encode(...)`, or `abi.encodePacked(...)` if `packed`, the members of
+// a tuple type being encoded as a sequence of parameters. Tuples are given as arrays, or as the objects the
+// decoding functions return, e.g. `{"arg0": ..., "arg1": ...}`.
+pub fn abi_encode(ty: &DynSolType, value: &Value, packed: bool) -> Result<Vec<u8>> {
+    let value = coerce_json(ty, value)?;
+
+    match packed {
+        true => Ok(value.abi_encode_packed()),
+        false => Ok(value.abi_encode_params())
+    }
+}
+
+// Coerces a JSON value to the given type, tuples being given as arrays or as the objects the decoding functions
+// return.
+pub fn coerce_json(ty: &DynSolType, value: &Value) -> Result<DynSolValue> {
+    Ok(ty.coerce_json(&tuples_as_arrays(ty, value))?)
+}
+
+fn tuples_as_arrays(ty: &DynSolType, value: &Value) -> Value {
+    match (ty, value) {
+        (DynSolType::Tuple(types), Value::Object(m)) if types.len() == m.len() => {
+            let values: Option<Vec<_>> = types.iter()
+                .enumerate()
+                .map(|(i, ty)| m.get(&format!("arg{i}")).map(|v| tuples_as_arrays(ty, v)))
+                .collect();
+            values.map(Value::Array).unwrap_or_else(|| value.clone())
+        },
+        (DynSolType::Tuple(types), Value::Array(values)) if types.len() == values.len() => {
+            Value::Array(types.iter().zip(values).map(|(ty, v)| tuples_as_arrays(ty, v)).collect())
+        },
+        (DynSolType::Array(ty) | DynSolType::FixedArray(ty, _), Value::Array(values)) => {
+            Value::Array(values.iter().map(|v| tuples_as_arrays(ty, v)).collect())
+        },
+        (_, v) => v.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{B256, I256};
+    use super::*;
+
+    #[test]
+    fn checks_values_fit_their_types() {
+        let int = |v: i64, bits| DynSolValue::Int(I256::try_from(v).unwrap(), bits);
+        let bytes = |size| DynSolValue::FixedBytes(B256::left_padding_from(&[0xff]), size);
+
+        assert!(is_in_range(&DynSolValue::Uint(U256::from(255), 8)));
+        assert!(!is_in_range(&DynSolValue::Uint(U256::from(256), 8)));
+        assert!(is_in_range(&DynSolValue::Uint(U256::MAX, 256)));
+        assert!(is_in_range(&int(127, 8)));
+        assert!(is_in_range(&int(-128, 8)));
+        assert!(!is_in_range(&int(128, 8)));
+        assert!(!is_in_range(&int(-129, 8)));
+        assert!(is_in_range(&int(-1, 256)));
+        assert!(is_in_range(&bytes(32)));
+        assert!(!is_in_range(&bytes(31)));
+        assert!(!is_in_range(&DynSolValue::Array(vec![DynSolValue::Tuple(vec![bytes(16)])])));
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use alloy::dyn_abi::{DynSolValue,DynSolType,Specifier};
+use super::abi_value::is_in_range;
+use super::decode_guard::guard_decoding;
+
+#[derive(Debug, Clone)]
+pub struct Constructor {
+    d: DynSolType,
+    c: alloy::json_abi::Constructor,
+    sig: String,
+    fullsig: String
+}
+
+impl Constructor {
+    pub fn new(c: alloy::json_abi::Constructor) -> Result<Self> {
+        let inputs = c.inputs
+            .iter()
+            .map(|p| p.resolve())
+            .collect::<Result<Vec<DynSolType>, alloy::dyn_abi::Error>>()?;
+
+        // Constructors are written like functions named `constructor`, without the `function` keyword.
+        let f = alloy::json_abi::Function {
+            name: "constructor".to_string(),
+            inputs: c.inputs.clone(),
+            outputs: vec![],
+            state_mutability: c.state_mutability
+        };
+
+        Ok(Constructor {
+            sig: f.signature(),
+            fullsig: f.full_signature().trim_start_matches("function ").to_string(),
+            d: DynSolType::Tuple(inputs),
+            c
+        })
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Self::new(alloy::json_abi::Constructor::parse(s)?)
+    }
+
+    pub fn sig(&self) -> &str {
+        &self.sig
+    }
+
+    pub fn fullsig(&self) -> &str {
+        &self.fullsig
+    }
+
+    pub fn inputs(&self) -> &Vec<alloy::json_abi::Param> {
+        &self.c.inputs
+    }
+
+    pub fn abi_decode_args(&self, data: &[u8]) -> Result<Vec<DynSolValue>, alloy::dyn_abi::Error> {
+        match guard_decoding(|| self.d.abi_decode_params(data))? {
+            DynSolValue::Tuple(values) => Ok(values),
+            v => Ok(vec![v])
+        }
+    }
+
+    // Whether the decoded values fit their types and encode back to the given arguments.
+    pub fn is_canonical_args(&self, values: &[DynSolValue], data: &[u8]) -> bool {
+        values.iter().all(is_in_range) && DynSolValue::Tuple(values.to_vec()).abi_encode_params() == data
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{bail, Result};
+
+// Keys starting the CBOR metadata Solidity appends to the runtime code, with their CBOR text header.
+const METADATA_KEYS: [&[u8]; 5] = [b"\x64ipfs", b"\x65bzzr0", b"\x65bzzr1", b"\x64solc", b"\x6cexperimental"];
+
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+const CODECOPY: u8 = 0x39;
+
+// Number of bytes after the push of the runtime length in which the init code copies the runtime, e.g.
+// `PUSH2 <length> DUP1 PUSH2 <offset> PUSH0 CODECOPY`.
+const CODECOPY_WINDOW: usize = 12;
+
+// What is known about the code preceding the constructor arguments in the input of a contract creation.
+#[derive(Debug, Clone, Copy)]
+pub enum CreationCode<'a> {
+    Unknown,
+    // The creation code of the contract, the input starts with.
+    Known(&'a [u8]),
+    // The length of the runtime code of the deployed contract.
+    RuntimeLength(usize)
+}
+
+// Offsets in the input of a contract creation where the constructor arguments may start, most likely first:
+// - the end of the creation code, when known,
+// - the end of the runtime code the init code copies with `CODECOPY`, when its length is known,
+// - the end of a Solidity CBOR metadata suffix, i.e. the end of the runtime code, from the last one.
+pub fn constructor_args_offsets(input: &[u8], code: CreationCode) -> Result<Vec<usize>> {
+    let mut offsets = match code {
+        CreationCode::Known(code) if input.starts_with(code) => return Ok(vec![code.len()]),
+        CreationCode::Known(_) => bail!("input does not start with the creation code"),
+        CreationCode::RuntimeLength(runtime_len) => codecopy_offsets(input, runtime_len),
+        CreationCode::Unknown => Vec::new()
+    };
+
+    for offset in metadata_offsets(input) {
+        if !offsets.contains(&offset) {
+            offsets.push(offset);
+        }
+    }
+
+    if offsets.is_empty() {
+        bail!("cannot locate the constructor arguments");
+    }
+
+    Ok(offsets)
+}
+
+// Ends of the runtime code copied by the init code, found from the pushes of its length followed by a
+// `CODECOPY` of that length from a pushed offset.
+fn codecopy_offsets(input: &[u8], runtime_len: usize) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut i = 0;
+
+    while let Some((len, next)) = next_op(input, i) {
+        if len == Some(runtime_len) {
+            let end = (next + CODECOPY_WINDOW).min(input.len());
+            if let Some(copy) = input[next..end].iter().position(|&op| op == CODECOPY) {
+                let mut j = next;
+                while let Some((offset, next)) = next_op(&input[..next + copy], j) {
+                    if let Some(end) = offset.and_then(|offset| offset.checked_add(runtime_len))
+                        && is_args_offset(input, end) && !offsets.contains(&end) {
+                        offsets.push(end);
+                    }
+                    j = next;
+                }
+            }
+        }
+        i = next;
+    }
+
+    offsets
+}
+
+// Decodes the opcode at `i`, returning the value it pushes if any, and the position of the next opcode.
+fn next_op(code: &[u8], i: usize) -> Option<(Option<usize>, usize)> {
+    let op = *code.get(i)?;
+    if !(PUSH1..=PUSH32).contains(&op) {
+        return Some((None, i + 1));
+    }
+
+    let n = usize::from(op - PUSH1) + 1;
+    let data = code.get(i + 1..i + 1 + n)?;
+    let value = match data.iter().position(|&b| b != 0) {
+        Some(start) if n - start > size_of::<usize>() => None,
+        _ => Some(data.iter().fold(0usize, |acc, &b| acc << 8 | usize::from(b)))
+    };
+
+    Some((value, i + 1 + n))
+}
+
+// Ends of the CBOR metadata suffixes, i.e. a CBOR map followed by its length on 2 bytes, where the arguments
+// could start, from the last one.
+fn metadata_offsets(input: &[u8]) -> Vec<usize> {
+    (2..=input.len())
+        .rev()
+        .step_by(32)
+        .filter(|&end| {
+            let len = usize::from(u16::from_be_bytes([input[end - 2], input[end - 1]]));
+            let Some(start) = (end - 2).checked_sub(len) else {
+                return false;
+            };
+
+            let metadata = &input[start..end - 2];
+            matches!(metadata.first(), Some(0xa1..=0xa5)) && METADATA_KEYS.iter().any(|k| metadata[1..].starts_with(k))
+        })
+        .collect()
+}
+
+// Whether the ABI-encoded arguments could start at `offset`, i.e. the rest of the input is made of words.
+fn is_args_offset(input: &[u8], offset: usize) -> bool {
+    offset <= input.len() && (input.len() - offset).is_multiple_of(32)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+    use crate::evm::abi::decoding::decode_constructor;
+    use crate::evm::abi::file_abi_item_provider::FileAbiItemProvider;
+    use super::*;
+
+    // `contract MyContract { constructor(bool) }`, built with solc 0.8.24 `--via-ir --optimize`: 204 bytes of
+    // runtime code, ending with 53 bytes of metadata, copied from offset 124.
+    const VIA_IR: &[u8] = &hex!("60803461006357601f61014838819003918201601f19168301916001600160401b038311848410176100675780849260209460405283398101031261006357518015158091036100635760ff80195f54169116175f5560405160cc908161007c8239f35b5f80fd5b634e487b7160e01b5f52604160045260245ffdfe60808060405260043610156011575f80fd5b5f3560e01c9081638bf1799f14607a575063b09a261614602f575f80fd5b346076576040366003190112607657602435801515810360765715606f57604060015b81516004356001600160a01b0316815260ff919091166020820152f35b60405f6052565b5f80fd5b346076575f36600319011260765760209060ff5f541615158152f3fea264697066735822122043709781c9bdc30c530978abf5db25a4b4ccfebf989baafd2ba404519a7f7e8264736f6c63430008180033");
+
+    // A contract without constructor arguments built with solc 0.8.25 (legacy pipeline): 324 bytes of runtime
+    // code copied from offset 28.
+    const LEGACY: &[u8] = &hex!("6080604052348015600e575f80fd5b506101448061001c5f395ff3fe60806040526004361061001d575f3560e01c8063785d04f514610021575b5f80fd5b61003461002f3660046100d5565b610036565b005b5f816001600160a01b0316836040515f6040518083038185875af1925050503d805f811461007f576040519150601f19603f3d011682016040523d82523d5f602084013e610084565b606091505b50509050806100d05760405162461bcd60e51b81526020600482015260146024820152734661696c656420746f2073656e64206d6f6e657960601b604482015260640160405180910390fd5b505050565b5f80604083850312156100e6575f80fd5b8235915060208301356001600160a01b0381168114610103575f80fd5b80915050925092905056fea2646970667358221220188e65dcedbc4bd68fdebc795292d5a9bf643385f138383969a28f796ff8858664736f6c63430008190033");
+
+    const METADATA_LEN: usize = 53;
+
+    // `abi.encode(true)`
+    const ARGS: [u8; 32] = hex!("0000000000000000000000000000000000000000000000000000000000000001");
+
+    fn input(code: &[u8], args: &[u8]) -> Vec<u8> {
+        [code, args].concat()
+    }
+
+    // `VIA_IR` as built with `--no-cbor-metadata`: the runtime code without its metadata, the pushed lengths of
+    // the creation and runtime codes reduced accordingly.
+    fn via_ir_without_metadata() -> Vec<u8> {
+        let mut code = VIA_IR[..VIA_IR.len() - METADATA_LEN].to_vec();
+        code[10..12].copy_from_slice(&((VIA_IR.len() - METADATA_LEN) as u16).to_be_bytes());
+        assert_eq!(code[89..91], hex!("60cc"));
+        code[90] = 0xcc - METADATA_LEN as u8;
+        code
+    }
+
+    #[test]
+    fn locates_constructor_arguments() {
+        let stripped = via_ir_without_metadata();
+        let cases: Vec<(&str, Vec<u8>, CreationCode, Vec<usize>)> = vec![
+            ("known code", input(VIA_IR, &ARGS), CreationCode::Known(VIA_IR), vec![328]),
+            ("runtime length", input(VIA_IR, &ARGS), CreationCode::RuntimeLength(204), vec![328]),
+            ("wrong runtime length", input(VIA_IR, &ARGS), CreationCode::RuntimeLength(203), vec![328]),
+            ("metadata", input(VIA_IR, &ARGS), CreationCode::Unknown, vec![328]),
+            ("no arguments", VIA_IR.to_vec(), CreationCode::Unknown, vec![328]),
+            ("legacy runtime length", LEGACY.to_vec(), CreationCode::RuntimeLength(324), vec![352]),
+            ("legacy metadata", input(LEGACY, &ARGS), CreationCode::Unknown, vec![352]),
+            ("known code without metadata", input(&stripped, &ARGS), CreationCode::Known(&stripped), vec![275]),
+            ("runtime length without metadata", input(&stripped, &ARGS), CreationCode::RuntimeLength(151), vec![275])
+        ];
+
+        for (name, input, code, expected) in cases {
+            assert_eq!(constructor_args_offsets(&input, code).unwrap(), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn decodes_constructor_arguments() {
+        let p = FileAbiItemProvider::from_inline(r#"["constructor(bool enabled)"]"#).unwrap();
+        let stripped = via_ir_without_metadata();
+        let decode = |input: &[u8], code| -> serde_json::Value {
+            serde_json::from_slice(&decode_constructor(&p, input, code).unwrap()).unwrap()
+        };
+
+        let decoded = decode(&input(VIA_IR, &ARGS), CreationCode::Unknown);
+        assert_eq!(decoded["value"]["fullsig"], "constructor(bool enabled)");
+        assert_eq!(decoded["value"]["inputs"]["arg0"], true);
+        assert_eq!(decode(&input(VIA_IR, &ARGS), CreationCode::Known(VIA_IR))["value"]["inputs"]["arg0"], true);
+        assert_eq!(decode(&input(&stripped, &ARGS), CreationCode::RuntimeLength(151))["value"]["inputs"]["arg0"], true);
+
+        // the arguments are missing, or the creation code is not the one of the input
+        assert!(decode_constructor(&p, VIA_IR, CreationCode::Unknown).is_err());
+        assert!(decode_constructor(&p, &input(VIA_IR, &ARGS), CreationCode::Known(LEGACY)).is_err());
+    }
+
+    #[test]
+    fn fails_to_locate_constructor_arguments() {
+        let stripped = via_ir_without_metadata();
+        let cases: Vec<(&str, Vec<u8>, CreationCode)> = vec![
+            ("other code", input(VIA_IR, &ARGS), CreationCode::Known(LEGACY)),
+            ("no metadata", input(&stripped, &ARGS), CreationCode::Unknown),
+            ("no metadata and wrong runtime length", input(&stripped, &ARGS), CreationCode::RuntimeLength(204)),
+            ("empty input", Vec::new(), CreationCode::Unknown)
+        ];
+
+        for (name, input, code) in cases {
+            assert!(constructor_args_offsets(&input, code).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn finds_runtime_code_copies() {
+        assert_eq!(codecopy_offsets(&input(VIA_IR, &ARGS), 204), vec![328]);
+        assert_eq!(codecopy_offsets(&input(LEGACY, &ARGS), 324), vec![352]);
+        assert_eq!(codecopy_offsets(&via_ir_without_metadata(), 151), vec![275]);
+
+        // the copy must end on a word boundary of the input
+        assert_eq!(codecopy_offsets(&input(VIA_IR, &ARGS[1..]), 204), Vec::<usize>::new());
+        assert_eq!(codecopy_offsets(&input(VIA_IR, &ARGS), 0xcd), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn finds_metadata_suffixes() {
+        assert_eq!(metadata_offsets(VIA_IR), vec![328]);
+        assert_eq!(metadata_offsets(&input(VIA_IR, &ARGS)), vec![328]);
+        assert_eq!(metadata_offsets(&input(VIA_IR, &[ARGS, ARGS].concat())), vec![328]);
+        assert_eq!(metadata_offsets(&input(LEGACY, &ARGS)), vec![352]);
+        assert_eq!(metadata_offsets(&via_ir_without_metadata()), Vec::<usize>::new());
+
+        // a metadata suffix is only looked for where the rest of the input is made of words
+        assert_eq!(metadata_offsets(&input(VIA_IR, &ARGS[1..])), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,60 @@
+use std::cell::Cell;
+use std::panic::{catch_unwind, set_hook, take_hook, AssertUnwindSafe};
+use std::sync::Once;
+use alloy::sol_types::Error::Overrun;
+
+thread_local! {
+    // Whether the thread is running a guarded decoding, whose panics are not reported.
+    static GUARDED: Cell<bool> = const { Cell::new(false) };
+}
+
+static QUIET_HOOK: Once = Once::new();
+
+// Runs an alloy decoding, turning a panic into an error. Its decoder multiplies the length of an array read from
+// the input without checking for overflow, which panics in debug builds when that length is huge. Such panics are
+// not printed, the row gets the error instead.
+pub fn guard_decoding<T, F>(f: F) -> Result<T, alloy::dyn_abi::Error>
+where
+    F: FnOnce() -> Result<T, alloy::dyn_abi::Error>
+{
+    QUIET_HOOK.call_once(|| {
+        let hook = take_hook();
+        set_hook(Box::new(move |info| {
+            if !GUARDED.get() {
+                hook(info);
+            }
+        }));
+    });
+
+    let guarded = GUARDED.replace(true);
+    let res = catch_unwind(AssertUnwindSafe(f));
+    GUARDED.set(guarded);
+
+    res.unwrap_or(Err(Overrun.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_panics_into_errors() {
+        let res: Result<(), _> = guard_decoding(|| panic!("overflow"));
+
+        assert!(matches!(res, Err(alloy::dyn_abi::Error::SolTypes(Overrun))));
+        assert!(!GUARDED.get());
+    }
+
+    #[test]
+    fn nests_guards() {
+        let res = guard_decoding(|| {
+            let inner: Result<(), _> = guard_decoding(|| panic!("overflow"));
+            assert!(inner.is_err());
+            assert!(GUARDED.get());
+            Ok(1)
+        });
+
+        assert_eq!(res.unwrap(), 1);
+        assert!(!GUARDED.get());
+    }
+}
@@ -0,0 +1,266 @@
+use std::borrow::Cow;
+use anyhow::{anyhow, Result};
+use alloy::primitives::B256;
+use alloy::dyn_abi::DynSolValue;
+use lazy_static::lazy_static;
+use super::abi_item_provider::{AbiItemProvider, Event, Function};
+use super::creation_code::{constructor_args_offsets, CreationCode};
+use super::json_encoding::{encode_call, encode_call_with, encode_constructor, encode_event, encode_nested_call, NestedCallDecoder};
+
+lazy_static! {
+    // The errors raised by `revert("...")` and `require(..., "...")`, and by panics, e.g. a division by zero.
+    static ref BUILTIN_ERRORS: Vec<Function> = ["function Error(string)", "function Panic(uint256)"]
+        .into_iter()
+        .filter_map(|s| Function::parse(s).ok())
+        .collect();
+}
+
+// Decodes a log with the first candidate event of the provider matching its topics and data,
+// and returns it JSON-encoded. The candidates are the events whose selector is the first topic, e.g. the
+// ERC-20 and ERC-721 `Transfer` events, then the anonymous events, and only the ones with as many topics
+// as the log are tried. The events without `indexed` markers of providers whose signatures may lack them
+// are tried with each layout of indexed parameters fitting the log. When there are several candidates, an
+// event whose values encode back to the log is preferred.
+pub fn decode_event(p: &dyn AbiItemProvider, topics: &[B256], data: &[u8]) -> Result<Vec<u8>> {
+    let anonymous = p.get_anonymous_events();
+    let events = match topics.first().ok_or(anyhow!("missing topic0")).and_then(|topic0| p.get_events(topic0.as_slice())) {
+        Ok(events) => events.into_iter().chain(anonymous).collect(),
+        Err(err) if anonymous.is_empty() => return Err(err),
+        Err(_) => anonymous
+    };
+
+    let unmarked = |evt: &Event| p.has_unmarked_events() && evt.inputs().iter().all(|input| !input.indexed);
+    let events: Vec<Cow<Event>> = events.into_iter()
+        .flat_map(|evt| match unmarked(evt) {
+            true => evt.indexed_layouts(topics.len()).into_iter().map(Cow::Owned).collect(),
+            false => vec![Cow::Borrowed(evt)]
+        })
+        .filter(|evt| evt.num_topics() == topics.len())
+        .collect();
+    let strict = p.is_strict() || events.len() > 1;
+    let mut fallback = None;
+    let mut last_err = anyhow!("no candidate event");
+
+    for evt in &events {
+        match evt.decode_log_parts(topics.iter().copied(), data) {
+            Ok(decoded) if !strict || evt.is_canonical_encoding(&decoded, topics, data) =>
+                return encode_event(evt, &decoded),
+            Ok(decoded) => {
+                last_err = anyhow!("{} does not re-encode to the log", evt.sig());
+                fallback = fallback.or(Some((evt, decoded)));
+            },
+            Err(err) => last_err = err.into()
+        }
+    }
+
+    match fallback {
+        Some((evt, decoded)) if !p.is_strict() => encode_event(evt, &decoded),
+        _ => Err(last_err)
+    }
+}
+
+// Decodes a call, and its output if any, with the first candidate function of the provider matching
+// its input, and returns it JSON-encoded. When several functions share the selector, a function whose
+// values encode back to the input is preferred.
+pub fn decode_call(p: &dyn AbiItemProvider, input: &[u8], output: Option<&[u8]>) -> Result<Vec<u8>> {
+    let (func, inputs, outputs) = find_call(p, input, output)?;
+    encode_call(func, inputs.iter(), outputs.as_ref().map(|o| o.iter()))
+}
+
+// Same as `decode_call`, also decoding in place the `bytes` inputs holding a call known to one of `providers`,
+// e.g. the calls of a `multicall(bytes[])` or the `data` of a Safe `execTransaction`, up to `max_depth` levels
+// of nested calls. A `bytes` value is only decoded when it is the exact encoding of the call, as any value
+// starting with a known selector would otherwise be taken for a call.
+pub fn decode_call_recursive(
+    p: &dyn AbiItemProvider,
+    providers: &[&dyn AbiItemProvider],
+    input: &[u8],
+    output: Option<&[u8]>,
+    max_depth: usize
+) -> Result<Vec<u8>> {
+    let (func, inputs, outputs) = find_call(p, input, output)?;
+    let nested_calls = |data: &[u8]| decode_nested_call(providers, data, max_depth);
+    let nested_calls: Option<&NestedCallDecoder> = if max_depth > 0 { Some(&nested_calls) } else { None };
+    encode_call_with(func, inputs.iter(), outputs.as_ref().map(|o| o.iter()), nested_calls)
+}
+
+fn decode_nested_call(providers: &[&dyn AbiItemProvider], data: &[u8], depth: usize) -> Option<Vec<u8>> {
+    if depth == 0 || data.len() < 4 {
+        return None;
+    }
+
+    let nested_calls = |data: &[u8]| decode_nested_call(providers, data, depth - 1);
+
+    providers.iter().find_map(|p| {
+        let (func, inputs, _) = find_call(*p, data, None).ok()?;
+
+        if !func.is_canonical_input(&inputs, data) {
+            return None;
+        }
+
+        encode_nested_call(func, inputs.iter(), Some(&nested_calls)).ok()
+    })
+}
+
+type DecodedCall<'a> = (&'a Function, Vec<DynSolValue>, Option<Vec<DynSolValue>>);
+
+fn find_call<'a>(p: &'a dyn AbiItemProvider, input: &[u8], output: Option<&[u8]>) -> Result<DecodedCall<'a>> {
+    let selector = input.get(0..4).ok_or(anyhow!("input is shorter than a selector"))?;
+    let funcs = p.get_functions(selector)?;
+    let strict = p.is_strict() || funcs.len() > 1;
+    let mut fallback = None;
+    let mut last_err = anyhow!("no candidate function");
+
+    for func in funcs {
+        let (inputs, canonical) = match func.abi_decode_input(&input[4..]) {
+            Ok(inputs) => {
+                let canonical = !strict || func.is_canonical_input(&inputs, input);
+                (inputs, canonical)
+            },
+            Err(err) => {
+                last_err = err.into();
+                continue;
+            }
+        };
+
+        let outputs = match output.map(|o| func.abi_decode_output(o)).transpose() {
+            Ok(outputs) => outputs,
+            Err(err) => {
+                last_err = err.into();
+                continue;
+            }
+        };
+
+        if canonical {
+            return Ok((func, inputs, outputs));
+        }
+
+        last_err = anyhow!("{} does not re-encode to the input", func.sig());
+        fallback = fallback.or(Some((func, inputs, outputs)));
+    }
+
+    match fallback {
+        Some(call) if !p.is_strict() => Ok(call),
+        _ => Err(last_err)
+    }
+}
+
+// Decodes the arguments appended to the creation code in the input of a contract creation, with the first
+// candidate constructor of the provider, at the first offset they can start at, see `constructor_args_offsets`,
+// and returns them JSON-encoded. When there are several candidate constructors or offsets, values whose
+// encoding is the exact rest of the input are preferred.
+pub fn decode_constructor(p: &dyn AbiItemProvider, input: &[u8], code: CreationCode) -> Result<Vec<u8>> {
+    let ctors = p.get_constructors()?;
+    let offsets = constructor_args_offsets(input, code)?;
+    let strict = p.is_strict() || ctors.len() > 1 || offsets.len() > 1;
+    let mut fallback = None;
+    let mut last_err = anyhow!("no candidate constructor");
+
+    for args in offsets.into_iter().map(|offset| &input[offset..]) {
+        for ctor in &ctors {
+            match ctor.abi_decode_args(args) {
+                Ok(values) if !strict || ctor.is_canonical_args(&values, args) =>
+                    return encode_constructor(ctor, values.iter()),
+                Ok(values) => {
+                    last_err = anyhow!("{} does not re-encode to the arguments", ctor.sig());
+                    fallback = fallback.or(Some((*ctor, values)));
+                },
+                Err(err) => last_err = err.into()
+            }
+        }
+    }
+
+    match fallback {
+        Some((ctor, values)) if !p.is_strict() => encode_constructor(ctor, values.iter()),
+        _ => Err(last_err)
+    }
+}
+
+// Decodes the output of a reverted call holding a builtin error, i.e. `Error(string)` or `Panic(uint256)`,
+// and returns it JSON-encoded.
+pub fn decode_revert(output: &[u8]) -> Result<Vec<u8>> {
+    let selector = output.get(0..4).ok_or(anyhow!("output is shorter than a selector"))?;
+    let func = BUILTIN_ERRORS.iter()
+        .find(|func| func.selector() == selector)
+        .ok_or(anyhow!("output is not a builtin error"))?;
+    let inputs = func.abi_decode_input(&output[4..])?;
+    encode_call(func, inputs.iter(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, U256};
+    use serde_json::{json, Value};
+    use super::super::file_abi_item_provider::FileAbiItemProvider;
+    use super::*;
+
+    const ABI: &str = r#"[
+        "function multicall(bytes[] data)",
+        "function transfer(address to, uint256 value) returns (bool)",
+        "function execute(bytes data) returns (bytes result)"
+    ]"#;
+
+    fn call(sig: &str, values: Vec<DynSolValue>) -> Vec<u8> {
+        Function::parse(sig).unwrap().abi_encode_input(&values).unwrap()
+    }
+
+    fn transfer() -> Vec<u8> {
+        let to = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        call("function transfer(address,uint256)", vec![DynSolValue::Address(to), DynSolValue::Uint(U256::from(1000), 256)])
+    }
+
+    fn multicall(calls: Vec<Vec<u8>>) -> Vec<u8> {
+        call("function multicall(bytes[])", vec![DynSolValue::Array(calls.into_iter().map(DynSolValue::Bytes).collect())])
+    }
+
+    fn decode(p: &FileAbiItemProvider, input: &[u8], output: Option<&[u8]>, max_depth: usize) -> Value {
+        serde_json::from_slice(&decode_call_recursive(p, &[p], input, output, max_depth).unwrap()).unwrap()
+    }
+
+    fn hex(data: &[u8]) -> Value {
+        json!(alloy::hex::encode_prefixed(data))
+    }
+
+    #[test]
+    fn decodes_nested_calls_up_to_max_depth() {
+        let p = FileAbiItemProvider::from_inline(ABI).unwrap();
+        let inner = multicall(vec![transfer()]);
+        let input = multicall(vec![inner.clone(), transfer()]);
+
+        let decoded = decode(&p, &input, None, 2);
+        let calls = &decoded["value"]["inputs"]["arg0"];
+        assert_eq!(calls[0]["inputs"]["arg0"][0]["signature"], "transfer(address,uint256)");
+        assert_eq!(calls[0]["inputs"]["arg0"][0]["inputs"]["arg1"], 1000);
+        assert_eq!(calls[1]["signature"], "transfer(address,uint256)");
+
+        let decoded = decode(&p, &input, None, 1);
+        let calls = &decoded["value"]["inputs"]["arg0"];
+        assert_eq!(calls[0]["signature"], "multicall(bytes[])");
+        assert_eq!(calls[0]["inputs"]["arg0"][0], hex(&transfer()));
+
+        let decoded = decode(&p, &input, None, 0);
+        assert_eq!(decoded["value"]["inputs"]["arg0"][0], hex(&inner));
+    }
+
+    #[test]
+    fn leaves_non_canonical_calls_as_bytes() {
+        let p = FileAbiItemProvider::from_inline(ABI).unwrap();
+        let trailing = [transfer(), vec![0]].concat();
+        let mut dirty = transfer();
+        dirty[4] = 0xff;
+
+        let decoded = decode(&p, &multicall(vec![trailing.clone(), dirty.clone()]), None, 2);
+        assert_eq!(decoded["value"]["inputs"]["arg0"], json!([hex(&trailing), hex(&dirty)]));
+    }
+
+    #[test]
+    fn leaves_outputs_as_bytes() {
+        let p = FileAbiItemProvider::from_inline(ABI).unwrap();
+        let input = call("function execute(bytes)", vec![DynSolValue::Bytes(transfer())]);
+        let output = DynSolValue::Tuple(vec![DynSolValue::Bytes(transfer())]).abi_encode_params();
+
+        let decoded = decode(&p, &input, Some(&output), 2);
+        assert_eq!(decoded["value"]["inputs"]["arg0"]["signature"], "transfer(address,uint256)");
+        assert_eq!(decoded["value"]["outputs"]["arg0"], hex(&transfer()));
+    }
+}
@@ -0,0 +1,192 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use alloy::primitives::keccak256;
+use chrono::{DateTime, Utc};
+use object_store::{ObjectMeta, ObjectStore};
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+// Fetched ABI documents, persisted in a directory shared by the workers, so that a document is only fetched
+// once per TTL whatever the number of processes. Stale documents are revalidated with their ETag or
+// Last-Modified date, and fetch failures are kept for a shorter TTL, so that a missing document is not
+// requested again for every block.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentCache {
+    dir: Option<PathBuf>,
+    ttl: Duration,
+    negative_ttl: Duration
+}
+
+// A document which does not exist, e.g. the ABI of a contract which is not verified, as opposed to a document
+// which cannot be fetched right now.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct DocumentNotFound(pub String);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DocumentMeta {
+    key: String,
+    fetched_at: u64,
+    e_tag: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+    error: Option<String>
+}
+
+impl DocumentCache {
+    pub fn new(dir: Option<PathBuf>, ttl: Duration, negative_ttl: Duration) -> Result<Self> {
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self { dir, ttl, negative_ttl })
+    }
+
+    // Gets the document at `path`, identified in the cache by `key`, e.g. its URL.
+    pub async fn get(&self, key: &str, objstr: &dyn ObjectStore, path: &Path) -> Result<Vec<u8>> {
+        let Some(dir) = &self.dir else {
+            return Ok(objstr.get(path).await?.bytes().await?.to_vec());
+        };
+
+        let name = keccak256(key.as_bytes()).to_string();
+        let meta_path = dir.join(format!("{name}.meta.json"));
+        let body_path = dir.join(format!("{name}.body"));
+
+        let meta: Option<DocumentMeta> = std::fs::read(&meta_path).ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .filter(|meta: &DocumentMeta| meta.key == key);
+        let body = std::fs::read(&body_path).ok();
+        let now = unix_now();
+
+        if let Some(meta) = &meta {
+            let ttl = if meta.error.is_some() { self.negative_ttl } else { self.ttl };
+            let fresh = now < meta.fetched_at.saturating_add(ttl.as_secs());
+
+            match (&meta.error, &body) {
+                (Some(err), _) if fresh => return Err(DocumentNotFound(format!("{err} (cached)")).into()),
+                (None, Some(body)) if fresh => return Ok(body.clone()),
+                _ => ()
+            }
+        }
+
+        // A previously fetched document, which can be revalidated or used when the store cannot be reached.
+        let cached = match (meta, body) {
+            (Some(meta), Some(body)) if meta.error.is_none() => Some((meta, body)),
+            _ => None
+        };
+
+        // Stores do not all report conditional requests the same way, hence the metadata of the document is
+        // compared with the cached one instead.
+        if let Some((meta, body)) = &cached {
+            match objstr.head(path).await {
+                Ok(head) if is_not_modified(meta, &head) => {
+                    write_meta(&meta_path, &DocumentMeta { fetched_at: now, ..meta.clone() })?;
+                    debug!(key, "revalidated ABI document");
+                    return Ok(body.clone());
+                },
+                Ok(_) | Err(object_store::Error::NotFound { .. }) => (),
+                Err(err) => {
+                    warn!(key, error = %err, "cannot revalidate ABI document, using the cached one");
+                    return Ok(body.clone());
+                }
+            }
+        }
+
+        match objstr.get(path).await {
+            Ok(res) => {
+                let e_tag = res.meta.e_tag.clone();
+                let last_modified = Some(res.meta.last_modified);
+                let content = res.bytes().await?.to_vec();
+
+                write_atomic(&body_path, &content)?;
+                write_meta(&meta_path, &DocumentMeta { key: key.to_string(), fetched_at: now, e_tag, last_modified, error: None })?;
+                debug!(key, "fetched ABI document");
+                Ok(content)
+            },
+            Err(err @ object_store::Error::NotFound { .. }) => {
+                let meta = DocumentMeta { key: key.to_string(), fetched_at: now, error: Some(err.to_string()), ..Default::default() };
+                write_meta(&meta_path, &meta)?;
+                Err(DocumentNotFound(err.to_string()).into())
+            },
+            Err(err) => match cached {
+                Some((_, body)) => {
+                    warn!(key, error = %err, "cannot fetch ABI document, using the cached one");
+                    Ok(body)
+                },
+                None => Err(err.into())
+            }
+        }
+    }
+
+    // Gets a document which never changes once it exists, e.g. the ABI of a verified contract, identified by `key`.
+    // It is only fetched when not cached yet: `fetch` returns `None` when the document does not exist, which is
+    // cached for the negative TTL, while its errors are not cached.
+    pub async fn get_immutable<F>(&self, key: &str, fetch: F) -> Result<Vec<u8>>
+    where
+        F: AsyncFnOnce() -> Result<Option<Vec<u8>>>
+    {
+        let Some(dir) = &self.dir else {
+            return fetch().await?.ok_or(DocumentNotFound("document not found".to_string()).into());
+        };
+
+        let name = keccak256(key.as_bytes()).to_string();
+        let meta_path = dir.join(format!("{name}.meta.json"));
+        let body_path = dir.join(format!("{name}.body"));
+        let now = unix_now();
+
+        let meta: Option<DocumentMeta> = std::fs::read(&meta_path).ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .filter(|meta: &DocumentMeta| meta.key == key);
+
+        match meta {
+            Some(DocumentMeta { error: None, .. }) => if let Ok(body) = std::fs::read(&body_path) {
+                return Ok(body);
+            },
+            Some(DocumentMeta { error: Some(err), fetched_at, .. }) if now < fetched_at.saturating_add(self.negative_ttl.as_secs()) => {
+                return Err(DocumentNotFound(format!("{err} (cached)")).into());
+            },
+            _ => ()
+        }
+
+        match fetch().await? {
+            Some(content) => {
+                write_atomic(&body_path, &content)?;
+                write_meta(&meta_path, &DocumentMeta { key: key.to_string(), fetched_at: now, ..Default::default() })?;
+                debug!(key, "fetched document");
+                Ok(content)
+            },
+            None => {
+                let err = "document not found".to_string();
+                write_meta(&meta_path, &DocumentMeta { key: key.to_string(), fetched_at: now, error: Some(err.clone()), ..Default::default() })?;
+                Err(DocumentNotFound(err).into())
+            }
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+fn write_meta(path: &std::path::Path, meta: &DocumentMeta) -> Result<()> {
+    write_atomic(path, &serde_json::to_vec(meta)?)
+}
+
+// Writes to a temporary file renamed over the target, so that concurrent workers never read a partial file.
+fn write_atomic(path: &std::path::Path, content: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// Whether a document is unchanged, according to its ETag if the store provides one, to its last modification
+// date otherwise.
+fn is_not_modified(meta: &DocumentMeta, head: &ObjectMeta) -> bool {
+    match (&meta.e_tag, &head.e_tag) {
+        (Some(cached), Some(current)) => cached == current,
+        _ => meta.last_modified == Some(head.last_modified)
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, bail, Context, Result};
+use url::Url;
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+use serde_json::Value;
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::Address;
+use alloy::transports::http::reqwest::{Client, Response, StatusCode};
+use alloy::transports::http::reqwest::header::RETRY_AFTER;
+use lazy_static::lazy_static;
+use tokio::time::sleep;
+use tracing::debug;
+use super::abi_item::AbiItem;
+use super::event::Event;
+use super::constructor::Constructor;
+use super::abi_item_provider::{AbiContext, AbiItemProvider, ContextScope};
+use super::document_cache::DocumentCache;
+use super::file_abi_item_provider::FileAbiItemProvider;
+
+const API_KEY_ENV: &str = "ETHERSCAN_API_KEY";
+// Retries of the requests failing with a rate limit error, an HTTP 429 or a 5xx, with an exponential backoff.
+const RATE_LIMITED_RETRIES: u32 = 5;
+
+lazy_static! {
+    static ref HTTP_CLIENT: Client = Client::new();
+    // Rate limiters, by API URL, shared by all the decoders of the process. Each worker process has its own.
+    static ref RATE_LIMITERS: Mutex<HashMap<String, Arc<TokenBucket>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    GetAbi,
+    GetSourceCode
+}
+
+#[serde_inline_default]
+#[derive(Debug, Deserialize)]
+struct EtherscanConfig {
+    #[serde_inline_default("https://api.etherscan.io/v2/api".to_string())]
+    url: String,
+
+    #[serde_inline_default(Action::GetAbi)]
+    action: Action,
+
+    // Requests per second of a worker: ClickHouse runs up to `pool_size` workers per function.
+    #[serde(alias = "rate-limit")]
+    #[serde_inline_default(0.25)]
+    rate_limit: f64
+}
+
+// Fetches the ABI of a verified contract through an Etherscan-compatible API, e.g. Etherscan or Blockscout.
+// The decoder is written `etherscan://<chain id>/<address>`, or `etherscan://<chain id>` for the address of
+// the row. Fetched ABIs are kept in the document cache, so that each contract is only fetched once.
+pub struct EtherscanAbiItemProvider {
+    inner: FileAbiItemProvider
+}
+
+impl EtherscanAbiItemProvider {
+    pub fn context_scope(s: &str) -> ContextScope {
+        match Url::parse(s).map(|u| u.path().trim_matches('/').is_empty()) {
+            Ok(false) => ContextScope::None,
+            _ => ContextScope::Address
+        }
+    }
+
+    pub async fn new(s: &str, ctx: &AbiContext, docs: &DocumentCache) -> Result<Self> {
+        let u = Url::parse(s).context("cannot parse URL")?;
+        let conf: EtherscanConfig = serde_qs::from_str(u.fragment().unwrap_or_default())
+            .context("cannot parse etherscan decoder options")?;
+        let chain: u64 = u.host_str().unwrap_or_default().parse().context("cannot parse chain id")?;
+        let address = match u.path().trim_matches('/') {
+            "" => ctx.address.ok_or(anyhow!("etherscan decoder requires the address of the contract"))?,
+            address => address.parse().context("cannot parse contract address")?
+        };
+
+        let key = format!("etherscan:{}:{}:{}", conf.url, chain, address);
+        let content = docs.get_immutable(&key, async || fetch_abi(&conf, chain, address).await).await
+            .with_context(|| format!("no verified ABI for {} on chain {}", address, chain))?;
+        let abi: JsonAbi = serde_json::from_slice(&content)?;
+
+        Ok(Self { inner: FileAbiItemProvider::from_json_abi(abi)? })
+    }
+}
+
+impl AbiItemProvider for EtherscanAbiItemProvider {
+    fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
+        self.inner.get_abi_item(selector)
+    }
+
+    fn get_abi_items(&self, selector: &[u8]) -> Result<Vec<&AbiItem>> {
+        self.inner.get_abi_items(selector)
+    }
+
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        self.inner.get_anonymous_events()
+    }
+
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        self.inner.get_constructors()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    status: String,
+    message: String,
+    result: Value
+}
+
+// Fetches the JSON ABI of a contract, or `None` if the contract is not verified.
+async fn fetch_abi(conf: &EtherscanConfig, chain: u64, address: Address) -> Result<Option<Vec<u8>>> {
+    let limiter = rate_limiter(&conf.url, conf.rate_limit);
+    let action = match conf.action {
+        Action::GetAbi => "getabi",
+        Action::GetSourceCode => "getsourcecode"
+    };
+
+    let mut query = vec![
+        ("chainid", chain.to_string()),
+        ("module", "contract".to_string()),
+        ("action", action.to_string()),
+        ("address", address.to_string())
+    ];
+
+    if let Ok(api_key) = std::env::var(API_KEY_ENV) {
+        query.push(("apikey", api_key));
+    }
+
+    for retry in 0..=RATE_LIMITED_RETRIES {
+        limiter.acquire().await;
+
+        let res = HTTP_CLIENT.get(&conf.url).query(&query).send().await?;
+        let status = res.status();
+
+        // The last attempt fails with the HTTP error.
+        if retry < RATE_LIMITED_RETRIES && (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) {
+            let wait = retry_after(&res).unwrap_or(Duration::from_secs(1 << retry));
+            debug!(%address, retry, %status, wait_ms = wait.as_millis() as u64, "etherscan request failed, retrying");
+            sleep(wait).await;
+            continue;
+        }
+
+        let res: ApiResponse = res.error_for_status()?
+            .json().await
+            .context("cannot parse etherscan response")?;
+
+        let abi = match (res.status.as_str(), &res.result) {
+            ("1", Value::String(abi)) if conf.action == Action::GetAbi => Some(abi.as_str()),
+            ("1", Value::Array(contracts)) => contracts.first()
+                .and_then(|contract| contract["ABI"].as_str())
+                .filter(|abi| abi.starts_with('[')),
+            (_, Value::String(err)) if err.to_lowercase().contains("rate limit") => {
+                debug!(%address, retry, "etherscan rate limit reached");
+                sleep(Duration::from_secs(1 << retry)).await;
+                continue;
+            },
+            (_, Value::String(err)) if err.to_lowercase().contains("not verified") => None,
+            (_, Value::String(err)) => bail!("etherscan error: {} ({})", err, res.message),
+            _ => bail!("etherscan error: {}", res.message)
+        };
+
+        return Ok(abi.map(|abi| abi.as_bytes().to_vec()));
+    }
+
+    bail!("etherscan rate limit reached")
+}
+
+// Delay requested by the `Retry-After` header, in seconds, if any.
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+fn rate_limiter(url: &str, rate: f64) -> Arc<TokenBucket> {
+    let mut limiters = RATE_LIMITERS.lock().unwrap_or_else(|err| err.into_inner());
+    limiters.entry(url.to_string()).or_insert_with(|| Arc::new(TokenBucket::new(rate))).clone()
+}
+
+// Token bucket allowing `rate` requests per second, in bursts of up to `rate` requests.
+struct TokenBucket {
+    rate: f64,
+    state: Mutex<(f64, Instant)>
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        let rate = rate.max(0.01);
+        Self { rate, state: Mutex::new((rate.max(1.0), Instant::now())) }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+                let (tokens, last) = *state;
+                let now = Instant::now();
+                let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.rate).min(self.rate.max(1.0));
+
+                if tokens >= 1.0 {
+                    *state = (tokens - 1.0, now);
+                    return;
+                }
+
+                *state = (tokens, now);
+                Duration::from_secs_f64((1.0 - tokens) / self.rate)
+            };
+
+            sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use super::*;
+
+    // Serves the given responses in order, one per connection, and returns the API URL and the request count.
+    async fn serve(responses: Vec<String>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+
+        tokio::spawn(async move {
+            for res in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let _ = stream.read(&mut [0; 4096]).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                stream.write_all(res.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!("HTTP/1.1 {status}\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n{body}", body.len())
+    }
+
+    fn config(url: String) -> EtherscanConfig {
+        EtherscanConfig { url, action: Action::GetAbi, rate_limit: 100.0 }
+    }
+
+    #[tokio::test]
+    async fn retries_http_429_and_5xx() {
+        let (url, requests) = serve(vec![
+            response("429 Too Many Requests", "retry-after: 0\r\n", ""),
+            response("503 Service Unavailable", "retry-after: 0\r\n", ""),
+            response("200 OK", "", r#"{"status":"1","message":"OK","result":"[]"}"#)
+        ]).await;
+
+        let abi = fetch_abi(&config(url), 1, Address::ZERO).await.unwrap();
+
+        assert_eq!(abi, Some(b"[]".to_vec()));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_http_4xx() {
+        let (url, requests) = serve(vec![response("404 Not Found", "", "")]).await;
+
+        assert!(fetch_abi(&config(url), 1, Address::ZERO).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn reads_unverified_contracts_as_missing() {
+        let body = r#"{"status":"0","message":"NOTOK","result":"Contract source code not verified"}"#;
+        let (url, _) = serve(vec![response("200 OK", "", body)]).await;
+
+        assert_eq!(fetch_abi(&config(url), 1, Address::ZERO).await.unwrap(), None);
+    }
+
+    async fn acquire(bucket: &TokenBucket, n: usize) {
+        for _ in 0..n {
+            bucket.acquire().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn allows_bursts_up_to_the_rate() {
+        let bucket = TokenBucket::new(50.0);
+        let start = Instant::now();
+
+        acquire(&bucket, 50).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        acquire(&bucket, 10).await;
+        assert!(start.elapsed() >= Duration::from_millis(180));
+    }
+
+    #[tokio::test]
+    async fn does_not_accumulate_more_than_a_burst() {
+        let bucket = TokenBucket::new(20.0);
+        sleep(Duration::from_millis(300)).await;
+        let start = Instant::now();
+
+        acquire(&bucket, 25).await;
+        assert!(start.elapsed() >= Duration::from_millis(220));
+    }
+
+    #[tokio::test]
+    async fn allows_a_request_at_a_time_below_one_per_second() {
+        let bucket = TokenBucket::new(0.25);
+        let start = Instant::now();
+
+        acquire(&bucket, 1).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert!(tokio::time::timeout(Duration::from_millis(300), bucket.acquire()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn shares_the_rate_between_tasks() {
+        let bucket = Arc::new(TokenBucket::new(100.0));
+        acquire(&bucket, 100).await;
+        let start = Instant::now();
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let bucket = bucket.clone();
+                tokio::spawn(async move { acquire(&bucket, 5).await })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(180));
+    }
+
+    #[test]
+    fn shares_rate_limiters_by_url() {
+        let a = rate_limiter("https://api.etherscan.io/v2/api", 5.0);
+        let b = rate_limiter("https://api.etherscan.io/v2/api", 1.0);
+        let c = rate_limiter("https://eth.blockscout.com/api", 5.0);
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+}
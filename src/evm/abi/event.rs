@@ -1,6 +1,9 @@
+use std::borrow::Cow;
 use anyhow::Result;
 use alloy::primitives::B256;
-use alloy::dyn_abi::{DynSolEvent,Specifier,DecodedEvent};
+use alloy::dyn_abi::{DynSolEvent,DynSolValue,Specifier,DecodedEvent};
+use super::abi_value::is_in_range;
+use super::decode_guard::guard_decoding;
 
 #[derive(Debug, Clone)]
 pub struct Event {
@@ -13,17 +16,22 @@ pub struct Event {
 
 impl Event {
     pub fn new(e: alloy::json_abi::Event) -> Result<Self> {
+        let fullsig = match e.anonymous {
+            true => e.full_signature().replacen("event ", "event anonymous ", 1),
+            false => e.full_signature()
+        };
+
         Ok(Event { 
             selector: e.selector(),
             sig: e.signature(),
-            fullsig: e.full_signature(),
+            fullsig,
             d: e.resolve()?,
             e
         })
     }
 
     pub fn parse(s: &str) -> Result<Self> {
-        Self::new(alloy::json_abi::Event::parse(s)?)
+        Self::new(alloy::json_abi::Event::parse(&normalize_anonymous(s))?)
     }
 
     pub fn selector(&self) -> B256 {
@@ -42,14 +50,206 @@ impl Event {
         &self.fullsig
     }
 
+    // Whether the logs of this event have no selector topic, in which case the event can only be found by
+    // trying to decode the log with it.
+    pub fn is_anonymous(&self) -> bool {
+        self.e.anonymous
+    }
+
+    // Number of topics of the logs of this event, i.e. the indexed parameters and the selector of
+    // non-anonymous events.
+    pub fn num_topics(&self) -> usize {
+        self.e.inputs.iter().filter(|input| input.indexed).count() + usize::from(!self.e.anonymous)
+    }
+
     pub fn inputs(&self) -> &Vec<alloy::json_abi::EventParam> {
         &self.e.inputs
     }
 
+    // The variants of this event with `indexed` parameters making logs of `num_topics` topics, for signatures
+    // which carry no `indexed` markers, e.g. the ones of 4byte-style databases. The layouts indexing the first
+    // parameters come first, as they are the most common ones.
+    pub fn indexed_layouts(&self, num_topics: usize) -> Vec<Event> {
+        let Some(num_indexed) = num_topics.checked_sub(usize::from(!self.e.anonymous)) else {
+            return Vec::new();
+        };
+
+        combinations(self.e.inputs.len(), num_indexed)
+            .into_iter()
+            .filter_map(|indexed| {
+                let mut e = self.e.clone();
+                for (i, input) in e.inputs.iter_mut().enumerate() {
+                    input.indexed = indexed.contains(&i);
+                }
+                Event::new(e).ok()
+            })
+            .collect()
+    }
+
     pub fn decode_log_parts<I>(&self, topics: I, data: &[u8]) -> Result<DecodedEvent, alloy::dyn_abi::Error>
     where
         I: IntoIterator<Item = B256>
     {
-        self.d.decode_log_parts(topics, data)
+        guard_decoding(|| self.d.decode_log_parts(topics, data))
+    }
+
+    // Whether the decoded values fit their types and encode back to the given topics and data, i.e. the input is
+    // the canonical encoding of the values and not just an input the decoder accepts.
+    pub fn is_canonical_encoding(&self, decoded: &DecodedEvent, topics: &[B256], data: &[u8]) -> bool {
+        let indexed_topics = topics.iter().skip(if self.e.anonymous { 0 } else { 1 });
+
+        decoded.indexed.len() == indexed_topics.len()
+            && decoded.indexed.iter().chain(&decoded.body).all(is_in_range)
+            && decoded.indexed.iter().zip(indexed_topics).all(|(v, t)| v.abi_encode() == t.as_slice())
+            && DynSolValue::Tuple(decoded.body.clone()).abi_encode_params() == data
+    }
+}
+
+// The sets of `k` positions out of `n`, in lexicographic order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+
+    (0..n)
+        .flat_map(|first| {
+            combinations(n - first - 1, k - 1)
+                .into_iter()
+                .map(move |rest| std::iter::once(first).chain(rest.into_iter().map(|i| first + 1 + i)).collect())
+        })
+        .collect()
+}
+
+// Rewrites an anonymous event fullsig written with the `anonymous` keyword after `event`, e.g.
+// `event anonymous Deposit(address indexed,uint256)`, to the Solidity order the parser expects, i.e.
+// `event Deposit(address indexed,uint256) anonymous`.
+pub fn normalize_anonymous(s: &str) -> Cow<'_, str> {
+    let rest = s.trim_start()
+        .strip_prefix("event")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .and_then(|rest| rest.trim_start().strip_prefix("anonymous"))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map(str::trim)
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$'));
+
+    // The keyword may be given at both places, e.g. `event anonymous Deposit(uint256) anonymous`.
+    let suffixed = |rest: &str| rest.rsplit_once(')').is_some_and(|(_, tail)| tail.trim() == "anonymous");
+
+    match rest {
+        Some(rest) if suffixed(rest) => Cow::Owned(format!("event {rest}")),
+        Some(rest) => Cow::Owned(format!("event {rest} anonymous")),
+        None => Cow::Borrowed(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+    use alloy::primitives::U256;
+    use serde_json::Value;
+    use crate::evm::abi::decoding::decode_event;
+    use crate::evm::abi::file_abi_item_provider::FileAbiItemProvider;
+    use super::*;
+
+    const TRANSFER: B256 = B256::new(hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"));
+    const FROM: B256 = B256::new(hex!("000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"));
+    const TO: B256 = B256::new(hex!("000000000000000000000000dac17f958d2ee523a2206206994597c13d831ec7"));
+
+    fn decode(p: &FileAbiItemProvider, topics: &[B256], data: &[u8]) -> Result<Value> {
+        Ok(serde_json::from_slice(&decode_event(p, topics, data)?)?)
+    }
+
+    #[test]
+    fn normalizes_anonymous_keyword() {
+        let cases = [
+            ("event anonymous Deposit(address indexed,uint256)", "event Deposit(address indexed,uint256) anonymous"),
+            ("  event \t anonymous\n  Deposit( address indexed , uint256 )  ", "event Deposit( address indexed , uint256 ) anonymous"),
+            ("event anonymous Deposit(uint256) anonymous", "event Deposit(uint256) anonymous"),
+            ("event anonymous Deposit(uint256)  anonymous  ", "event Deposit(uint256)  anonymous"),
+            ("event anonymous Deposit(uint256 anonymous)", "event Deposit(uint256 anonymous) anonymous"),
+            ("event anonymous _Deposit(uint256)", "event _Deposit(uint256) anonymous"),
+            // unchanged: already in the Solidity order, or `anonymous` is a name
+            ("event Deposit(address indexed,uint256) anonymous", "event Deposit(address indexed,uint256) anonymous"),
+            ("event Deposit(uint256 anonymous)", "event Deposit(uint256 anonymous)"),
+            ("event anonymous(uint256)", "event anonymous(uint256)"),
+            ("event anonymousDeposit(uint256)", "event anonymousDeposit(uint256)"),
+            ("eventanonymous Deposit(uint256)", "eventanonymous Deposit(uint256)"),
+            ("function anonymous(uint256)", "function anonymous(uint256)")
+        ];
+
+        for (s, expected) in cases {
+            assert_eq!(normalize_anonymous(s), expected, "{s}");
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lists_indexed_layouts_by_topic_count() {
+        let evt = Event::parse("event Transfer(address,address,uint256)").unwrap();
+        let layouts = |num_topics| -> Vec<String> {
+            evt.indexed_layouts(num_topics).iter().map(|e| e.fullsig().to_string()).collect()
+        };
+
+        assert_eq!(layouts(1), ["event Transfer(address, address, uint256)"]);
+        assert_eq!(layouts(3), [
+            "event Transfer(address indexed, address indexed, uint256)",
+            "event Transfer(address indexed, address, uint256 indexed)",
+            "event Transfer(address, address indexed, uint256 indexed)"
+        ]);
+        assert!(layouts(0).is_empty());
+        assert!(layouts(5).is_empty());
+
+        let evt = Event::parse("event anonymous Deposit(address,uint256)").unwrap();
+        assert_eq!(evt.indexed_layouts(1).len(), 2);
+    }
+
+    #[test]
+    fn decodes_anonymous_events_by_topic_count() {
+        let p = FileAbiItemProvider::from_inline(r#"[
+            {"type":"event","name":"Deposit","anonymous":true,"inputs":[{"name":"dst","type":"address","indexed":true},{"name":"wad","type":"uint256","indexed":false}]},
+            {"type":"event","name":"Log","anonymous":true,"inputs":[{"name":"wad","type":"uint256","indexed":false}]}
+        ]"#).unwrap();
+        let wad = B256::from(U256::from(1000));
+
+        let decoded = decode(&p, &[FROM], wad.as_slice()).unwrap();
+        assert_eq!(decoded["value"]["fullsig"], "event anonymous Deposit(address indexed dst, uint256 wad)");
+        assert_eq!(decoded["value"]["inputs"]["arg1"], 1000);
+
+        // without topics, or with a first topic which is not the selector of a known event
+        assert_eq!(decode(&p, &[], wad.as_slice()).unwrap()["value"]["signature"], "Log(uint256)");
+        assert!(decode(&p, &[FROM, TO], wad.as_slice()).is_err());
+    }
+
+    #[test]
+    fn prefers_candidates_encoding_back_to_the_log() {
+        let p = FileAbiItemProvider::from_inline(r#"[
+            "event Transfer(address indexed from, address to, uint256 indexed value)",
+            "event Transfer(address indexed from, address indexed to, uint256 value)"
+        ]"#).unwrap();
+        let fullsig = |topics: &[B256], data: &[u8]| decode(&p, topics, data).unwrap()["value"]["fullsig"].clone();
+
+        // A value above 2^160 is not an address: the first candidate does not re-encode to the log.
+        let large = B256::from(U256::from(1) << 200);
+        assert_eq!(fullsig(&[TRANSFER, FROM, TO], large.as_slice()), "event Transfer(address indexed from, address indexed to, uint256 value)");
+
+        // Both candidates decode the small value, the first one is used.
+        let value = B256::from(U256::from(1000));
+        assert_eq!(fullsig(&[TRANSFER, FROM, TO], value.as_slice()), "event Transfer(address indexed from, address to, uint256 indexed value)");
+
+        // No candidate re-encodes to the log, as `to` is not an address either: the first one decoding it is used.
+        assert_eq!(fullsig(&[TRANSFER, FROM, large], large.as_slice()), "event Transfer(address indexed from, address to, uint256 indexed value)");
+    }
+
+    #[test]
+    fn parses_normalized_anonymous_events() {
+        let cases = [
+            "event anonymous Deposit(address indexed,uint256)",
+            "event anonymous Deposit(address indexed,uint256) anonymous",
+            "event anonymous Deposit(uint256 anonymous)"
+        ];
+
+        for s in cases {
+            let item = alloy::json_abi::AbiItem::parse(&normalize_anonymous(s)).unwrap();
+            assert!(matches!(item, alloy::json_abi::AbiItem::Event(e) if e.anonymous), "{s}");
+        }
+    }
+}
@@ -1,48 +1,261 @@
 use std::collections::HashMap;
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use url::Url;
+use object_store::ObjectStore;
+use object_store::path::Path;
 use alloy::json_abi::JsonAbi;
-use arrow::datatypes::ToByteSlice;
-use object_store::parse_url;
+use tracing::warn;
 use super::event::Event;
 use super::function::Function;
+use super::constructor::Constructor;
 use super::abi_item::AbiItem;
 use super::abi_item_provider::AbiItemProvider;
+use super::object_store_url::parse_object_store_url;
+use super::document_cache::DocumentCache;
+use super::abi_bundle::{is_glob, list_glob, parse_abi_bundle, split_glob};
+
+// Documents loaded from a directory URL, i.e. ending with `/`.
+const DIRECTORY_PATTERN: &str = "**/*.json";
 
 pub struct FileAbiItemProvider {
-    m: HashMap<Vec<u8>,AbiItem>
+    m: HashMap<Vec<u8>, Vec<AbiItem>>,
+    anonymous: Vec<Event>,
+    constructors: Vec<Constructor>
 }
 
 impl FileAbiItemProvider {
-    pub async fn new(s: &str) -> Result<Self> {
+    // Loads a single ABI document, or all the documents of a directory (URL ending with `/`) or matching a glob,
+    // e.g. `file:///project/out/**/*.json`, through the document cache.
+    pub async fn new(s: &str, docs: &DocumentCache) -> Result<Self> {
         let u = Url::parse(s).context("cannot parse URL")?;
-        let (objstr, path) = parse_url(&u)?;
-        let content = objstr.get(&path).await?.bytes().await?;
-        let abi: JsonAbi = serde_json::from_str(std::str::from_utf8(&content)?)?;
-        let mut m: HashMap<Vec<u8>, AbiItem> = HashMap::new();
-
-        for abi_item in abi.into_items() {
-            match abi_item {
-                alloy::json_abi::AbiItem::Event(e) => {
-                    let s = e.selector();
-                    let item = AbiItem::Event(Event::new(e.into_owned())?);
-                     m.insert(s.to_byte_slice().to_vec(), item); 
-                },
-                alloy::json_abi::AbiItem::Function(f) => { 
-                    let s = f.selector();
-                    let item = AbiItem::Function(Function::new(f.into_owned())?);
-                    m.insert(s.to_vec(), item); 
-                },
-                _ => ()
+
+        // A `?` wildcard starts the query of the URL: it is part of the path for the stores that can be listed.
+        let path = match u.query() {
+            Some(query) if !matches!(u.scheme(), "http" | "https") => format!("{}?{}", u.path(), query),
+            _ => u.path().to_string()
+        };
+
+        if !path.ends_with('/') && !is_glob(&path) {
+            let (objstr, path) = parse_object_store_url(&u)?;
+            return Self::from_object_store(s, objstr.as_ref(), &path, None, docs).await;
+        }
+
+        let (dir, pattern) = split_glob(&path);
+        let pattern = if pattern.is_empty() { DIRECTORY_PATTERN } else { pattern };
+        let mut dir_url = u.clone();
+        dir_url.set_path(dir);
+        dir_url.set_query(None);
+
+        let (objstr, dir) = parse_object_store_url(&dir_url)?;
+        Self::from_object_store(s, objstr.as_ref(), &dir, Some(pattern), docs).await
+    }
+
+    // Loads the document at `path` or, given a glob pattern, the documents below `path` matching it. `s` is the
+    // URL the documents are cached under.
+    pub async fn from_object_store(
+        s: &str,
+        objstr: &dyn ObjectStore,
+        path: &Path,
+        pattern: Option<&str>,
+        docs: &DocumentCache
+    ) -> Result<Self> {
+        let Some(pattern) = pattern else {
+            let content = docs.get(s, objstr, path).await?;
+            return Self::from_json_abis(parse_abi_bundle(path.as_ref(), &content)?);
+        };
+
+        let paths = list_glob(objstr, path, pattern).await?;
+        if paths.is_empty() {
+            bail!("no ABI document found at {}", s);
+        }
+
+        let mut abis = Vec::new();
+        for path in paths {
+            let content = docs.get(&format!("{s}#{path}"), objstr, &path).await?;
+            abis.extend(parse_abi_bundle(path.as_ref(), &content)?);
+        }
+
+        Self::from_json_abis(abis)
+    }
+
+    // Loads an ABI document given inline, e.g. `[{"type":"event",...}]`.
+    pub fn from_inline(s: &str) -> Result<Self> {
+        Self::from_json_abis(parse_abi_bundle("inline", s.as_bytes())?)
+    }
+
+    pub fn from_json_abi(abi: JsonAbi) -> Result<Self> {
+        Self::from_json_abis([(String::new(), abi)])
+    }
+
+    // Merges named ABIs into one selector map. Different items sharing a selector, e.g. the ERC-20 and ERC-721
+    // `Transfer` events, are all kept as candidates, in order, and the collision is reported. Anonymous events
+    // and constructors have no selector and are kept apart.
+    pub fn from_json_abis(abis: impl IntoIterator<Item = (String, JsonAbi)>) -> Result<Self> {
+        let mut m: HashMap<Vec<u8>, Vec<(AbiItem, String)>> = HashMap::new();
+        let mut anonymous: Vec<Event> = Vec::new();
+        let mut constructors: Vec<Constructor> = Vec::new();
+
+        for (name, abi) in abis {
+            for abi_item in abi.into_items() {
+                let item = match abi_item {
+                    alloy::json_abi::AbiItem::Event(e) if e.anonymous => {
+                        let evt = Event::new(e.into_owned())?;
+                        if !anonymous.iter().any(|a| a.fullsig() == evt.fullsig()) {
+                            anonymous.push(evt);
+                        }
+                        continue;
+                    },
+                    alloy::json_abi::AbiItem::Constructor(c) => {
+                        let ctor = Constructor::new(c.into_owned())?;
+                        if !constructors.iter().any(|c| c.fullsig() == ctor.fullsig()) {
+                            constructors.push(ctor);
+                        }
+                        continue;
+                    },
+                    alloy::json_abi::AbiItem::Event(e) => AbiItem::Event(Event::new(e.into_owned())?),
+                    alloy::json_abi::AbiItem::Function(f) => AbiItem::Function(Function::new(f.into_owned())?),
+                    _ => continue
+                };
+
+                let candidates = m.entry(item.selector()).or_default();
+                if candidates.iter().any(|(c, _)| c.fullsig() == item.fullsig()) {
+                    continue;
+                }
+
+                if let Some((first, first_from)) = candidates.first() {
+                    warn!(
+                        selector = alloy::hex::encode_prefixed(item.selector()),
+                        first = first.fullsig(), first_from = first_from.as_str(),
+                        candidate = item.fullsig(), candidate_from = name.as_str(),
+                        "ABI selector collision"
+                    );
+                }
+
+                candidates.push((item, name.clone()));
             }
         }
-       
-        Ok(Self { m })
+
+        Ok(Self {
+            m: m.into_iter()
+                .map(|(selector, candidates)| (selector, candidates.into_iter().map(|(item, _)| item).collect()))
+                .collect(),
+            anonymous,
+            constructors
+        })
     }
 }
 
 impl AbiItemProvider for FileAbiItemProvider {
     fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
-        self.m.get(selector).ok_or(anyhow!("no ABI field found for selector"))
+        self.get_abi_items(selector)?.into_iter().next().ok_or(anyhow!("no ABI field found for selector"))
+    }
+
+    fn get_abi_items(&self, selector: &[u8]) -> Result<Vec<&AbiItem>> {
+        let items = self.m.get(selector).ok_or(anyhow!("no ABI field found for selector"))?;
+        Ok(items.iter().collect())
     }
-}
\ No newline at end of file
+
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        self.anonymous.iter().collect()
+    }
+
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        if self.constructors.is_empty() {
+            bail!("abi has no constructor");
+        }
+
+        Ok(self.constructors.iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+    use alloy::primitives::{B256, U256};
+    use object_store::memory::InMemory;
+    use object_store::PutPayload;
+    use crate::evm::abi::decoding::{decode_call, decode_event};
+    use super::*;
+
+    const TRANSFER_EVENT: [u8; 32] = hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+    const TRANSFER: [u8; 4] = hex!("a9059cbb");
+    const APPROVE: [u8; 4] = hex!("095ea7b3");
+
+    async fn store() -> InMemory {
+        let objstr = InMemory::new();
+        let docs = [
+            ("abis/erc20.json", r#"["event Transfer(address indexed from, address indexed to, uint256 value)"]"#),
+            ("abis/tokens/transfer.json", r#"{"abi": ["function transfer(address to, uint256 value) returns (bool)"]}"#),
+            ("abis/tokens/approve.txt", r#"["function approve(address spender, uint256 value) returns (bool)"]"#),
+            ("other/approve.json", r#"["function approve(address spender, uint256 value) returns (bool)"]"#)
+        ];
+
+        for (path, content) in docs {
+            objstr.put(&Path::from(path), PutPayload::from_static(content.as_bytes())).await.unwrap();
+        }
+
+        objstr
+    }
+
+    async fn load(path: &str, pattern: Option<&str>) -> Result<FileAbiItemProvider> {
+        let objstr = store().await;
+        FileAbiItemProvider::from_object_store(path, &objstr, &Path::from(path), pattern, &DocumentCache::default()).await
+    }
+
+    #[tokio::test]
+    async fn loads_a_document() {
+        let p = load("abis/erc20.json", None).await.unwrap();
+
+        assert!(p.get_abi_item(&TRANSFER_EVENT).is_ok());
+        assert!(p.get_abi_item(&TRANSFER).is_err());
+    }
+
+    #[tokio::test]
+    async fn loads_the_documents_matching_a_pattern() {
+        let p = load("abis", Some(DIRECTORY_PATTERN)).await.unwrap();
+
+        assert!(p.get_abi_item(&TRANSFER_EVENT).is_ok());
+        assert!(p.get_abi_item(&TRANSFER).is_ok());
+        assert!(p.get_abi_item(&APPROVE).is_err());
+
+        let p = load("abis", Some("*.json")).await.unwrap();
+
+        assert!(p.get_abi_item(&TRANSFER_EVENT).is_ok());
+        assert!(p.get_abi_item(&TRANSFER).is_err());
+    }
+
+    #[test]
+    fn keeps_and_decodes_colliding_items() {
+        let p = FileAbiItemProvider::from_inline(r#"[
+            "event Transfer(address indexed from, address indexed to, uint256 value)",
+            "event Transfer(address indexed from, address indexed to, uint256 indexed tokenId)",
+            "function collate_propagate_storage(bytes16)",
+            "function burn(uint256)"
+        ]"#).unwrap();
+        let from = B256::left_padding_from(&hex!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"));
+        let to = B256::left_padding_from(&hex!("dac17f958d2ee523a2206206994597c13d831ec7"));
+        let value = B256::from(U256::from(1000));
+        let decoded = |res: Result<Vec<u8>>| -> serde_json::Value { serde_json::from_slice(&res.unwrap()).unwrap() };
+
+        assert_eq!(p.get_events(&TRANSFER_EVENT).unwrap().len(), 2);
+        assert_eq!(p.get_functions(&hex!("42966c68")).unwrap().len(), 2);
+
+        let erc20 = decoded(decode_event(&p, &[TRANSFER_EVENT.into(), from, to], value.as_slice()));
+        assert_eq!(erc20["value"]["fullsig"], "event Transfer(address indexed from, address indexed to, uint256 value)");
+        assert_eq!(erc20["value"]["inputs"]["arg2"], 1000);
+
+        let erc721 = decoded(decode_event(&p, &[TRANSFER_EVENT.into(), from, to, value], &[]));
+        assert_eq!(erc721["value"]["fullsig"], "event Transfer(address indexed from, address indexed to, uint256 indexed tokenId)");
+        assert_eq!(erc721["value"]["inputs"]["arg2"], 1000);
+
+        // The first candidate leaves non-zero bytes in the padding of the `bytes16`: the second one is used.
+        let burn = hex!("42966c6800000000000000000000000000000000000000000000000000000000000003e8");
+        assert_eq!(decoded(decode_call(&p, &burn, None))["value"]["signature"], "burn(uint256)");
+    }
+
+    #[tokio::test]
+    async fn fails_without_matching_document() {
+        assert!(load("abis", Some("**/*.abi")).await.is_err());
+        assert!(load("abis/missing.json", None).await.is_err());
+    }
+}
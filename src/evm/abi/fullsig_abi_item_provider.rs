@@ -1,5 +1,7 @@
 use anyhow::{bail, Result};
 use super::abi_item::AbiItem;
+use super::event::Event;
+use super::constructor::Constructor;
 use super::abi_item_provider::AbiItemProvider;
 
 pub struct FullsigAbiItemProvider {
@@ -17,8 +19,22 @@ impl AbiItemProvider for FullsigAbiItemProvider {
     fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
         match &self.item {
             AbiItem::Function(func) if func.selector() == selector => Ok(&self.item),
-            AbiItem::Event(evt) if evt.selector() == selector => Ok(&self.item),
+            AbiItem::Event(evt) if !evt.is_anonymous() && evt.selector() == selector => Ok(&self.item),
             _ => bail!("selector does not match") 
         }
     }
+
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        match &self.item {
+            AbiItem::Event(evt) if evt.is_anonymous() => vec![evt],
+            _ => Vec::new()
+        }
+    }
+
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        match &self.item {
+            AbiItem::Constructor(ctor) => Ok(vec![ctor]),
+            _ => bail!("abi item is not a constructor")
+        }
+    }
 }
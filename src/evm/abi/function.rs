@@ -1,6 +1,8 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use alloy::primitives::Selector;
 use alloy::dyn_abi::{DynSolValue,DynSolType,DynSolCall,DynSolReturns,Specifier};
+use super::abi_value::{coerce_json, is_in_range};
+use super::decode_guard::guard_decoding;
 
 #[derive(Debug, Clone)]
 
@@ -60,17 +62,22 @@ impl Function {
     }
 
     pub fn abi_decode_input(&self, data: &[u8]) -> Result<Vec<DynSolValue>, alloy::dyn_abi::Error> {
-        self.d.abi_decode_input(data)
+        guard_decoding(|| self.d.abi_decode_input(data))
     }
 
     pub fn abi_decode_output(&self, data: &[u8]) -> Result<Vec<DynSolValue>, alloy::dyn_abi::Error> {
-        self.d.abi_decode_output(data)
+        guard_decoding(|| self.d.abi_decode_output(data))
     }
 
     pub fn abi_encode_input(&self, values: &[DynSolValue]) -> Result<Vec<u8>, alloy::dyn_abi::Error> {
         self.d.abi_encode_input(values)
     }
 
+    // Whether the decoded values fit their types and encode back to the given input, selector included.
+    pub fn is_canonical_input(&self, values: &[DynSolValue], input: &[u8]) -> bool {
+        values.iter().all(is_in_range) && self.abi_encode_input(values).is_ok_and(|encoded| encoded == input)
+    }
+
     pub fn inputs(&self) -> &Vec<alloy::json_abi::Param> {
         &self.f.inputs
     }
@@ -79,10 +86,18 @@ impl Function {
         &self.f.outputs
     }
 
-    pub fn coerce_inputs(&self, inputs: &[serde_json::Value]) -> Result<Vec<DynSolValue>, alloy::dyn_abi::Error>
-    {
-        (0..self.f.inputs.len())
-            .map(|i| self.f.inputs[i].resolve()?.coerce_json(&inputs[i]))
+    pub fn coerce_inputs(&self, inputs: &[serde_json::Value]) -> Result<Vec<DynSolValue>> {
+        if inputs.len() != self.f.inputs.len() {
+            bail!("{} expects {} arguments, got {}", self.sig, self.f.inputs.len(), inputs.len());
+        }
+
+        self.f.inputs
+            .iter()
+            .zip(inputs)
+            .enumerate()
+            .map(|(i, (param, input))| {
+                coerce_json(&param.resolve()?, input).map_err(|err| anyhow!("invalid argument {} ({}): {}", i, param.ty, err))
+            })
             .collect()
     }
 }
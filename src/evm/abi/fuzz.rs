@@ -0,0 +1,282 @@
+// Property tests feeding random ABIs and random, mostly malformed, inputs to the decoders and the JSON
+// encoder: whatever the input, they must return a value or an error, never panic, as a panic fails the
+// whole block of the ClickHouse query.
+use alloy::dyn_abi::{DynSolType, DynSolValue};
+use alloy::primitives::B256;
+use anyhow::Result;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use serde_json::Value;
+use super::abi_item::AbiItem;
+use super::abi_item_provider::AbiItemProvider;
+use super::abi_value::{abi_decode, abi_encode, parse_type};
+use super::constructor::Constructor;
+use super::creation_code::{constructor_args_offsets, CreationCode};
+use super::decoding::{decode_call, decode_call_recursive, decode_constructor, decode_event, decode_revert};
+use super::event::Event;
+use super::fullsig_abi_item_provider::FullsigAbiItemProvider;
+use super::function::Function;
+use super::json_encoding::encode_value;
+use super::trace_decoding::{decode_trace, parse_trace};
+
+// The same item as a lenient and as a strict provider, to go through both decoding paths.
+struct StrictProvider(FullsigAbiItemProvider);
+
+impl AbiItemProvider for StrictProvider {
+    fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
+        self.0.get_abi_item(selector)
+    }
+
+    fn is_strict(&self) -> bool {
+        true
+    }
+
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        self.0.get_anonymous_events()
+    }
+
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        self.0.get_constructors()
+    }
+}
+
+fn providers(fullsig: &str) -> Option<(FullsigAbiItemProvider, StrictProvider)> {
+    let lenient = FullsigAbiItemProvider::new(fullsig).ok()?;
+    let strict = StrictProvider(FullsigAbiItemProvider::new(fullsig).ok()?);
+    Some((lenient, strict))
+}
+
+fn sol_type() -> impl Strategy<Value = DynSolType> {
+    let leaf = prop_oneof![
+        Just(DynSolType::Bool),
+        Just(DynSolType::Address),
+        Just(DynSolType::Function),
+        Just(DynSolType::Bytes),
+        Just(DynSolType::String),
+        (1..=32usize).prop_map(|n| DynSolType::Uint(n * 8)),
+        (1..=32usize).prop_map(|n| DynSolType::Int(n * 8)),
+        (1..=32usize).prop_map(DynSolType::FixedBytes)
+    ];
+
+    leaf.prop_recursive(3, 24, 4, |inner| prop_oneof![
+        inner.clone().prop_map(|ty| DynSolType::Array(Box::new(ty))),
+        (inner.clone(), 1..4usize).prop_map(|(ty, n)| DynSolType::FixedArray(Box::new(ty), n)),
+        prop::collection::vec(inner, 1..4).prop_map(DynSolType::Tuple)
+    ])
+}
+
+fn params() -> impl Strategy<Value = Vec<DynSolType>> {
+    prop::collection::vec(sol_type(), 0..4)
+}
+
+fn param_list(types: &[DynSolType]) -> String {
+    types.iter().map(|ty| ty.sol_type_name().into_owned()).collect::<Vec<_>>().join(",")
+}
+
+// Words which are either small numbers, i.e. plausible offsets and lengths, or random, optionally followed by
+// a partial word, so that dynamic types get decoded past their head now and then.
+fn abi_data() -> impl Strategy<Value = Vec<u8>> {
+    let word = prop_oneof![
+        (0..=256u64).prop_map(|n| B256::left_padding_from(&n.to_be_bytes()).to_vec()),
+        Just(vec![0xff; 32]),
+        prop::collection::vec(any::<u8>(), 32)
+    ];
+
+    (prop::collection::vec(word, 0..12), prop::collection::vec(any::<u8>(), 0..32), any::<bool>())
+        .prop_map(|(words, tail, with_tail)| {
+            let mut data = words.concat();
+            if with_tail {
+                data.extend(tail);
+            }
+            data
+        })
+}
+
+fn json_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        "0x[0-9a-f]{0,66}".prop_map(Value::String),
+        ".{0,8}".prop_map(Value::String)
+    ];
+
+    leaf.prop_recursive(3, 24, 4, |inner| prop_oneof![
+        prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+        prop::collection::btree_map("arg[0-3]", inner, 0..4).prop_map(|m| Value::Object(m.into_iter().collect()))
+    ])
+}
+
+// Whether the decoded value fits its type, the decoder accepting e.g. any word for an `int8`, which re-encodes
+// to the same word but is not a valid `int8`.
+fn fits_type(value: &DynSolValue) -> bool {
+    match value {
+        DynSolValue::Int(i, size) => i.wrapping_shl(256 - size).asr(256 - size) == *i,
+        DynSolValue::Uint(u, size) => u.bit_len() <= *size,
+        DynSolValue::FixedBytes(b, size) => b[*size..].iter().all(|&b| b == 0),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) | DynSolValue::Tuple(values) =>
+            values.iter().all(fits_type),
+        _ => true
+    }
+}
+
+fn assert_json(res: &Result<Vec<u8>>) {
+    if let Ok(js) = res {
+        assert!(serde_json::from_slice::<Value>(js).is_ok(), "invalid JSON: {}", String::from_utf8_lossy(js));
+    }
+}
+
+proptest! {
+    #[test]
+    fn function_decoding_never_panics(types in params(), data in abi_data(), output in abi_data(), cut in any::<Index>()) {
+        let fullsig = format!("function f({})({})", param_list(&types), param_list(&types));
+        let Some((lenient, strict)) = providers(&fullsig) else {
+            return Ok(());
+        };
+        let Ok(func) = Function::parse(&fullsig) else {
+            return Ok(());
+        };
+
+        let input = [func.selector().as_slice(), &data].concat();
+        let input = &input[..cut.index(input.len() + 1)];
+        let nested: [&dyn AbiItemProvider; 1] = [&lenient];
+
+        for p in [&lenient as &dyn AbiItemProvider, &strict] {
+            assert_json(&decode_call(p, input, None));
+            assert_json(&decode_call(p, input, Some(&output)));
+            assert_json(&decode_call_recursive(p, &nested, input, None, 2));
+        }
+    }
+
+    #[test]
+    fn canonical_calls_round_trip(types in params(), data in abi_data()) {
+        let Ok(func) = Function::parse(&format!("function f({})", param_list(&types))) else {
+            return Ok(());
+        };
+        let Ok(values) = func.abi_decode_input(&data) else {
+            return Ok(());
+        };
+
+        let input = [func.selector().as_slice(), &data].concat();
+        if !func.is_canonical_input(&values, &input) || !values.iter().all(fits_type) {
+            return Ok(());
+        }
+
+        let p = FullsigAbiItemProvider::new(func.fullsig()).unwrap();
+        let js: Value = serde_json::from_slice(&decode_call(&p, &input, None).unwrap()).unwrap();
+        let args: Vec<Value> = (0..types.len()).map(|i| js["value"]["inputs"][format!("arg{i}")].clone()).collect();
+
+        prop_assert_eq!(func.abi_encode_input(&func.coerce_inputs(&args).unwrap()).unwrap(), input);
+    }
+
+    #[test]
+    fn coercing_inputs_never_panics(types in params(), args in prop::collection::vec(json_value(), 0..5)) {
+        if let Ok(func) = Function::parse(&format!("function f({})", param_list(&types))) {
+            let _ = func.coerce_inputs(&args).map(|values| func.abi_encode_input(&values));
+        }
+    }
+
+    #[test]
+    fn event_decoding_never_panics(
+        types in params(),
+        indexed in prop::collection::vec(any::<bool>(), 4),
+        anonymous in any::<bool>(),
+        topics in prop::collection::vec(any::<[u8; 32]>(), 0..5),
+        data in abi_data()
+    ) {
+        let inputs: Vec<String> = types.iter()
+            .zip(&indexed)
+            .map(|(ty, indexed)| format!("{}{}", ty.sol_type_name(), if *indexed { " indexed" } else { "" }))
+            .collect();
+        let fullsig = format!("event E({}){}", inputs.join(","), if anonymous { " anonymous" } else { "" });
+        let Some((lenient, strict)) = providers(&fullsig) else {
+            return Ok(());
+        };
+
+        let mut topics: Vec<B256> = topics.into_iter().map(B256::from).collect();
+        if let (Some(topic0), Ok(evt)) = (topics.first_mut(), Event::parse(&fullsig)) && !anonymous {
+            *topic0 = evt.selector();
+        }
+
+        for p in [&lenient as &dyn AbiItemProvider, &strict] {
+            assert_json(&decode_event(p, &topics, &data));
+        }
+    }
+
+    #[test]
+    fn constructor_decoding_never_panics(
+        types in params(),
+        code in prop::collection::vec(any::<u8>(), 0..96),
+        data in abi_data(),
+        runtime_len in 0..128usize
+    ) {
+        let Some((lenient, strict)) = providers(&format!("constructor({})", param_list(&types))) else {
+            return Ok(());
+        };
+
+        let input = [code.as_slice(), &data].concat();
+
+        for code in [CreationCode::Unknown, CreationCode::Known(&code), CreationCode::RuntimeLength(runtime_len)] {
+            let _ = constructor_args_offsets(&input, code);
+            for p in [&lenient as &dyn AbiItemProvider, &strict] {
+                assert_json(&decode_constructor(p, &input, code));
+            }
+        }
+    }
+
+    #[test]
+    fn value_decoding_never_panics(ty in sol_type(), data in abi_data(), packed in any::<bool>()) {
+        let ty = parse_type(&ty.sol_type_name()).unwrap();
+
+        if let Ok(value) = abi_decode(&ty, &data) {
+            let js: Value = serde_json::from_slice(&encode_value(&value).unwrap()).unwrap();
+
+            // Canonical data encodes back from its JSON value.
+            if value.abi_encode_params() == data && fits_type(&value) {
+                prop_assert_eq!(abi_encode(&ty, &js["value"], false).unwrap(), data);
+            }
+
+            let _ = abi_encode(&ty, &js["value"], packed);
+        }
+    }
+
+    #[test]
+    fn value_encoding_never_panics(ty in sol_type(), value in json_value(), packed in any::<bool>()) {
+        let _ = abi_encode(&ty, &value, packed);
+    }
+
+    #[test]
+    fn parsing_never_panics(s in ".{0,64}", bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+        let _ = parse_type(&s);
+        let _ = AbiItem::parse(&s);
+        let _ = decode_revert(&bytes);
+        if let Ok(trace) = parse_trace(&bytes) {
+            let _ = decode_trace(&trace, |_| Vec::new(), true);
+        }
+    }
+
+    #[test]
+    fn revert_decoding_never_panics(selector in prop_oneof![Just([0x08, 0xc3, 0x79, 0xa0]), Just([0x4e, 0x48, 0x7b, 0x71])], data in abi_data()) {
+        assert_json(&decode_revert(&[selector.as_slice(), &data].concat()));
+    }
+}
+
+#[test]
+fn wide_tuples_are_encoded() {
+    let ty = DynSolType::Tuple(vec![DynSolType::Uint(8); 1100]);
+    let value = DynSolValue::Tuple(vec![DynSolValue::Uint(Default::default(), 8); 1100]);
+    let js: Value = serde_json::from_slice(&encode_value(&value).unwrap()).unwrap();
+
+    assert_eq!(js["value"]["arg1099"], 0);
+    assert_eq!(abi_encode(&ty, &js["value"], false).unwrap(), value.abi_encode_params());
+}
+
+#[test]
+fn short_calldata_is_an_error() {
+    let p = FullsigAbiItemProvider::new("function transfer(address,uint256)").unwrap();
+
+    for input in [&[][..], &[0xa9], &[0xa9, 0x05, 0x9c]] {
+        assert!(decode_call(&p, input, None).is_err());
+    }
+}
@@ -1,10 +1,10 @@
 use std::io::Write;
 use anyhow::{anyhow, Context, Result};
-use serde_json::ser::{Formatter,CompactFormatter};
+use serde_json::ser::Formatter;
 use alloy::hex::ToHexExt;
 use alloy::primitives::Bytes;
 use alloy::dyn_abi::{DecodedEvent,DynSolValue};
-use crate::evm::abi::{Event, Function};
+use crate::evm::abi::{Constructor, Event, Function};
 use lazy_static::lazy_static;
 use crate::json::format_escaped_str_contents;
 
@@ -14,52 +14,125 @@ lazy_static! {
     };
 }
 
+fn arg_name(i: usize) -> String {
+    positional_arg_names.get(i).cloned().unwrap_or_else(|| format!("arg{}", i))
+}
+
+// Decodes the call held by a `bytes` value, e.g. a call of a `multicall(bytes[])`, returning the JSON-encoded
+// call without the `Result` envelope, or `None` to write the bytes as they are.
+pub type NestedCallDecoder<'a> = dyn Fn(&[u8]) -> Option<Vec<u8>> + 'a;
+
+// Compact JSON formatter, writing the `bytes` values holding a call as the decoded call when given a nested
+// call decoder, which is only given for the inputs of calls.
+#[derive(Clone, Copy, Default)]
+pub struct ValueFormatter<'a> {
+    nested_calls: Option<&'a NestedCallDecoder<'a>>
+}
+
+impl Formatter for ValueFormatter<'_> {}
+
 pub fn encode_call<'a, I>(func: &Function, inputs: I, outputs: Option<I>) -> Result<Vec<u8>> 
 where 
     I: IntoIterator<Item = &'a DynSolValue>, 
+{
+    encode_call_with(func, inputs, outputs, None)
+}
+
+// Same as `encode_call`, the `bytes` values being decoded with `nested_calls` if given.
+pub fn encode_call_with<'a, I>(
+    func: &Function,
+    inputs: I,
+    outputs: Option<I>,
+    nested_calls: Option<&NestedCallDecoder>
+) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a DynSolValue>,
 {
     let mut w = Vec::<u8>::with_capacity(1024);
-    let mut f = CompactFormatter;
+    let mut f = ValueFormatter { nested_calls };
 
     f.begin_object(&mut w)?;
     f.write_object_key(&mut w, "value", true)?;
     f.begin_object_value(&mut w)?;
-    f.begin_object(&mut w)?;
+    write_signed_values(&mut f, &mut w, func.sig(), func.fullsig(), inputs, outputs)?;
+    f.end_object_value(&mut w)?;
+    f.end_object(&mut w)?;
+    Ok(w)
+}
 
-    f.write_key_value_str(&mut w, "signature", func.sig(), true)?;
-    f.write_key_value_str(&mut w, "fullsig", func.fullsig(), false)?;
+// Encodes a call nested in a `bytes` value, i.e. without the `Result` envelope.
+pub fn encode_nested_call<'a, I>(func: &Function, inputs: I, nested_calls: Option<&NestedCallDecoder>) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a DynSolValue>,
+{
+    let mut w = Vec::<u8>::with_capacity(1024);
+    let mut f = ValueFormatter { nested_calls };
+    write_signed_values(&mut f, &mut w, func.sig(), func.fullsig(), inputs, None)?;
+    Ok(w)
+}
 
-    f.write_object_key(&mut w, "inputs", false)?;
+pub fn encode_constructor<'a, I>(ctor: &Constructor, inputs: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a DynSolValue>,
+{
+    let mut w = Vec::<u8>::with_capacity(1024);
+    let mut f = ValueFormatter::default();
+
+    f.begin_object(&mut w)?;
+    f.write_object_key(&mut w, "value", true)?;
     f.begin_object_value(&mut w)?;
-    f.write_values_as_object(&mut w, inputs.into_iter()
+    write_signed_values(&mut f, &mut w, ctor.sig(), ctor.fullsig(), inputs, None)?;
+    f.end_object_value(&mut w)?;
+    f.end_object(&mut w)?;
+    Ok(w)
+}
+
+fn write_signed_values<'a, I, W>(
+    f: &mut ValueFormatter,
+    w: &mut W,
+    sig: &str,
+    fullsig: &str,
+    inputs: I,
+    outputs: Option<I>
+) -> Result<()>
+where
+    I: IntoIterator<Item = &'a DynSolValue>,
+    W: ?Sized + Write
+{
+    f.begin_object(w)?;
+
+    f.write_key_value_str(w, "signature", sig, true)?;
+    f.write_key_value_str(w, "fullsig", fullsig, false)?;
+
+    f.write_object_key(w, "inputs", false)?;
+    f.begin_object_value(w)?;
+    f.write_values_as_object(w, inputs.into_iter()
         .enumerate()
-        .map(|(i, v)| (positional_arg_names[i].clone(), v))
+        .map(|(i, v)| (arg_name(i), v))
     )?;
 
-    f.end_object_value(&mut w)?;
+    f.end_object_value(w)?;
 
-    if outputs.is_some() {
-        f.write_object_key(&mut w, "outputs", false)?;
-        f.begin_object_value(&mut w)?;
-        f.write_values_as_object(&mut w, outputs
-            .unwrap()
+    // Only the inputs may hold nested calls, the outputs are written as they are.
+    if let Some(outputs) = outputs {
+        let f = &mut ValueFormatter::default();
+        f.write_object_key(w, "outputs", false)?;
+        f.begin_object_value(w)?;
+        f.write_values_as_object(w, outputs
             .into_iter()
             .enumerate()
-            .map(|(i, v)| (positional_arg_names[i].clone(), v))
+            .map(|(i, v)| (arg_name(i), v))
         )?;
-        f.end_object_value(&mut w)?;
+        f.end_object_value(w)?;
     }
 
-    f.end_object(&mut w)?;
-    f.end_object_value(&mut w)?;
-    f.end_object(&mut w)?;
-    Ok(w)
+    f.end_object(w).context("failed to write call")
 }
 
 pub fn encode_event(evt: &Event, dec_evt: &DecodedEvent) -> Result<Vec<u8>>
 {
     let mut w = Vec::<u8>::with_capacity(1024);
-    let mut f = CompactFormatter;
+    let mut f = ValueFormatter::default();
     let mut indexed_iter = dec_evt.indexed.iter();
     let mut body_iter = dec_evt.body.iter();
 
@@ -81,7 +154,7 @@ pub fn encode_event(evt: &Event, dec_evt: &DecodedEvent) -> Result<Vec<u8>>
             body_iter.next().ok_or(anyhow!("not enough unindexed values"))?
         };
 
-        f.write_key_value(&mut w, &positional_arg_names[i], v, i==0)?;
+        f.write_key_value(&mut w, &arg_name(i), v, i==0)?;
     }
 
     f.end_object(&mut w)?;
@@ -96,7 +169,7 @@ where
     I: IntoIterator<Item = (String, &'a DynSolValue)>
 {
     let mut w = Vec::<u8>::with_capacity(1024);
-    let mut f = CompactFormatter;
+    let mut f = ValueFormatter::default();
     f.begin_object(&mut w)?;
     f.write_object_key(&mut w, "value", true)?;
     f.begin_object_value(&mut w)?;
@@ -106,6 +179,18 @@ where
     Ok(w)
 }
 
+pub fn encode_value(value: &DynSolValue) -> Result<Vec<u8>> {
+    let mut w = Vec::<u8>::with_capacity(1024);
+    let mut f = ValueFormatter::default();
+    f.begin_object(&mut w)?;
+    f.write_object_key(&mut w, "value", true)?;
+    f.begin_object_value(&mut w)?;
+    f.write_value(&mut w, value)?;
+    f.end_object_value(&mut w)?;
+    f.end_object(&mut w)?;
+    Ok(w)
+}
+
 pub trait CompactFormatterExt {
     fn write_single_fragment_string<W>(&mut self, w: &mut W, s: &str) -> Result<()> 
     where 
@@ -142,7 +227,7 @@ pub trait CompactFormatterExt {
         W: ?Sized + Write;
 }
 
-impl CompactFormatterExt for CompactFormatter {
+impl CompactFormatterExt for ValueFormatter<'_> {
     #[inline(always)]
     fn write_single_fragment_string<W>(&mut self, w: &mut W, s: &str) -> Result<()>
     where
@@ -180,13 +265,12 @@ impl CompactFormatterExt for CompactFormatter {
         match value {
             DynSolValue::Bool(b) => 
                 self.write_bool(w, *b).context("failed to write Bool"),
-            DynSolValue::String(s) => {
-                self.begin_string(w)?;
-                self.write_string(w, s)?;
-                self.end_string(w).context("failed to write String")
-            }
-            DynSolValue::Bytes(data) => 
-                self.write_single_fragment_string(w, &Bytes::copy_from_slice(data).to_string()),
+            DynSolValue::String(s) => 
+                self.write_string(w, s).context("failed to write String"),
+            DynSolValue::Bytes(data) => match self.nested_calls.and_then(|nested_calls| nested_calls(data)) {
+                Some(call) => w.write_all(&call).context("failed to write nested call"),
+                None => self.write_single_fragment_string(w, &Bytes::copy_from_slice(data).to_string())
+            },
             DynSolValue::FixedBytes(data, _) => 
                 self.write_single_fragment_string(w, &data.to_string()),
             DynSolValue::Address(addr) => 
@@ -197,10 +281,11 @@ impl CompactFormatterExt for CompactFormatter {
                 self.write_u32(w, i.to::<u32>()).context("failed to write Uint"),
             DynSolValue::Uint(i, _) => 
                 self.write_single_fragment_string(w, &i.to_string()),
-            DynSolValue::Int(i, size, ) if *size <= 32 => 
-                self.write_i32(w, i.as_i32()).context("failed to write Int"),
-            DynSolValue::Int(i, _) => 
-                self.write_single_fragment_string(w, &i.to_string()),
+            // The decoder does not check that the value of a small int fits its size.
+            DynSolValue::Int(i, size) => match i32::try_from(*i) {
+                Ok(i) if *size <= 32 => self.write_i32(w, i).context("failed to write Int"),
+                _ => self.write_single_fragment_string(w, &i.to_string())
+            },
             DynSolValue::Array(values) => 
                 self.write_values_as_array(w, values),
             DynSolValue::FixedArray(values) =>
@@ -209,13 +294,13 @@ impl CompactFormatterExt for CompactFormatter {
                 self.write_values_as_object(w, values
                     .iter()
                     .enumerate()
-                    .map(|(i, v)| (positional_arg_names[i].clone(), v))
+                    .map(|(i, v)| (arg_name(i), v))
                 ),
             DynSolValue::CustomStruct { prop_names, tuple, .. } => 
-                self.write_values_as_object(w, tuple
+                self.write_values_as_object(w, prop_names
                     .iter()
-                    .enumerate()
-                    .map(|(i, v)| (prop_names[i].clone(), v))
+                    .cloned()
+                    .zip(tuple)
                 )
         }
     }
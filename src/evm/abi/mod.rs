@@ -1,14 +1,31 @@
 mod event;
 mod function;
+mod constructor;
 mod abi_item;
 mod abi_item_provider;
 mod file_abi_item_provider;
+mod abi_bundle;
+mod document_cache;
+mod object_store_url;
 mod fullsig_abi_item_provider;
+mod sourcify_abi_item_provider;
+mod sigdb_abi_item_provider;
+mod proxy_abi_item_provider;
+mod etherscan_abi_item_provider;
+mod decode_guard;
+pub mod abi_value;
+pub mod creation_code;
+pub mod decoding;
+pub mod trace_decoding;
 pub mod json_encoding;
 pub mod abi_item_ext;
+#[cfg(test)]
+mod fuzz;
 
 pub use event::Event;
 pub use function::Function;
+pub use constructor::Constructor;
 pub use abi_item::AbiItem;
-pub use abi_item_provider::{AbiItemProvider,AbiItemProviderFactory};
+pub use abi_item_provider::{AbiContext,AbiItemProvider,AbiItemProviderFactory,ContextScope};
+pub use document_cache::{DocumentCache,DocumentNotFound};
 pub use abi_item_ext::AbiItemExt;
\ No newline at end of file
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use url::Url;
+use object_store::{parse_url_opts, ObjectStore};
+use object_store::path::Path;
+
+// URL schemes of the cloud object stores: Amazon S3 (and compatible stores, e.g. MinIO), Google Cloud Storage
+// and Azure Blob Storage.
+const CLOUD_SCHEMES: [&str; 8] = ["s3://", "s3a://", "gs://", "az://", "adl://", "azure://", "abfs://", "abfss://"];
+
+// Prefixes of the environment variables configuring the cloud object stores, e.g. `AWS_ACCESS_KEY_ID`,
+// `AWS_ENDPOINT` or `GOOGLE_SERVICE_ACCOUNT`.
+const ENV_PREFIXES: [&str; 3] = ["AWS_", "GOOGLE_", "AZURE_"];
+
+pub fn is_cloud_url(s: &str) -> bool {
+    CLOUD_SCHEMES.iter().any(|scheme| s.starts_with(scheme))
+}
+
+// Same as `object_store::parse_url`, with the credentials and options of the cloud object stores read
+// from the environment.
+pub fn parse_object_store_url(u: &Url) -> Result<(Box<dyn ObjectStore>, Path)> {
+    Ok(parse_url_opts(u, object_store_options(u, std::env::vars()))?)
+}
+
+// Maps the environment variables of the cloud object stores to `object_store` options, e.g. `AWS_REGION`
+// to `aws_region`.
+fn object_store_options(u: &Url, vars: impl Iterator<Item = (String, String)>) -> Vec<(String, String)> {
+    // The HTTP store refuses plain `http://` URLs unless explicitly allowed.
+    let allow_http = (u.scheme() == "http").then(|| ("allow_http".to_string(), "true".to_string()));
+
+    vars
+        .filter(|(k, _)| ENV_PREFIXES.iter().any(|prefix| k.starts_with(prefix)))
+        .map(|(k, v)| (k.to_ascii_lowercase(), v))
+        .chain(allow_http)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn maps_cloud_env_vars_to_options() {
+        let u = Url::parse("s3://bucket/abis/").unwrap();
+        let opts = object_store_options(&u, vars(&[
+            ("AWS_ACCESS_KEY_ID", "key"),
+            ("AWS_ENDPOINT", "http://localhost:9000"),
+            ("GOOGLE_SERVICE_ACCOUNT", "/etc/sa.json"),
+            ("AZURE_STORAGE_ACCOUNT_NAME", "account"),
+            ("HOME", "/root"),
+            ("MY_AWS_SECRET", "secret")
+        ]));
+
+        assert_eq!(opts, vec![
+            ("aws_access_key_id".to_string(), "key".to_string()),
+            ("aws_endpoint".to_string(), "http://localhost:9000".to_string()),
+            ("google_service_account".to_string(), "/etc/sa.json".to_string()),
+            ("azure_storage_account_name".to_string(), "account".to_string())
+        ]);
+    }
+
+    #[test]
+    fn allows_http_for_http_urls_only() {
+        let allow_http = ("allow_http".to_string(), "true".to_string());
+
+        let u = Url::parse("http://localhost:8080/abis/erc20.json").unwrap();
+        assert_eq!(object_store_options(&u, vars(&[])), vec![allow_http.clone()]);
+
+        let u = Url::parse("https://example.com/abis/erc20.json").unwrap();
+        assert!(!object_store_options(&u, vars(&[])).contains(&allow_http));
+    }
+
+    #[test]
+    fn options_are_accepted_by_object_store() {
+        let u = Url::parse("s3://bucket/abis/erc20.json").unwrap();
+        let opts = object_store_options(&u, vars(&[
+            ("AWS_REGION", "eu-west-1"),
+            ("AWS_ACCESS_KEY_ID", "key"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+            ("AWS_PROFILE", "default")
+        ]));
+        let (_, path) = parse_url_opts(&u, opts).unwrap();
+
+        assert_eq!(path.as_ref(), "abis/erc20.json");
+    }
+
+    #[test]
+    fn detects_cloud_urls() {
+        assert!(is_cloud_url("s3://bucket/abi.json"));
+        assert!(is_cloud_url("gs://bucket/abi.json"));
+        assert!(is_cloud_url("abfss://container@account.dfs.core.windows.net/abi.json"));
+        assert!(!is_cloud_url("file:///abi.json"));
+        assert!(!is_cloud_url("https://example.com/abi.json"));
+    }
+}
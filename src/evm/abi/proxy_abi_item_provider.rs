@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, bail, Context, Result};
+use url::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use alloy::primitives::{b256, hex, Address, B256};
+use lazy_static::lazy_static;
+use quick_cache::sync::Cache;
+use tracing::debug;
+use crate::evm::rpc::{RpcCall, RpcClient, RpcResult};
+use super::abi_item::AbiItem;
+use super::event::Event;
+use super::constructor::Constructor;
+use super::abi_item_provider::{AbiContext, AbiItemProvider, AbiItemProviderFactory};
+use super::document_cache::DocumentCache;
+
+// bytes32(uint256(keccak256('eip1967.proxy.implementation')) - 1)
+const EIP1967_IMPLEMENTATION_SLOT: B256 = b256!("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+// bytes32(uint256(keccak256('eip1967.proxy.beacon')) - 1)
+const EIP1967_BEACON_SLOT: B256 = b256!("a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50");
+// keccak256('PROXIABLE')
+const EIP1822_PROXIABLE_SLOT: B256 = b256!("c5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7");
+
+// Runtime code of the EIP-1167 minimal proxies, around the address of the implementation.
+const EIP1167_PREFIX: [u8; 10] = hex!("363d3d373d3d3d363d73");
+const EIP1167_SUFFIX: [u8; 15] = hex!("5af43d82803e903d91602b57fd5bf3");
+
+// Selector of `implementation()`, implemented by the beacons.
+const BEACON_IMPLEMENTATION_SELECTOR: [u8; 4] = hex!("5c60da1b");
+
+const IMPLEMENTATION_CACHE_SIZE: usize = 1000;
+
+type SharedAbiItemProvider = Arc<dyn AbiItemProvider + Send + Sync>;
+
+lazy_static! {
+    // RPC clients, by URL, shared by all the proxy decoders of the process.
+    static ref RPC_CLIENTS: Mutex<HashMap<String, Arc<RpcClient>>> = Mutex::new(HashMap::new());
+
+    // Providers of the implementations, by decoder and context, shared by the proxies and blocks resolving to
+    // the same implementation, as the resolution is done for each block.
+    static ref IMPLEMENTATIONS: Cache<(String, AbiContext), SharedAbiItemProvider> = Cache::new(IMPLEMENTATION_CACHE_SIZE);
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxyConfig {
+    rpc: String,
+    source: String
+}
+
+impl ProxyConfig {
+    fn parse(s: &str) -> Result<Self> {
+        let u = Url::parse(s).context("cannot parse URL")?;
+        serde_qs::from_str(u.query().unwrap_or_default()).context("cannot parse proxy decoder options")
+    }
+}
+
+// Resolves the implementation of the contract of the row, when it is a proxy, at the block of the row, and loads
+// the ABI of the implementation from another decoder. The ABI of the contract itself is loaded when it is not a
+// proxy.
+pub struct ProxyAbiItemProvider {
+    inner: SharedAbiItemProvider
+}
+
+impl ProxyAbiItemProvider {
+    pub async fn new(s: &str, ctx: &AbiContext, docs: &DocumentCache) -> Result<Self> {
+        let conf = ProxyConfig::parse(s)?;
+        let address = ctx.address.ok_or(anyhow!("proxy decoder requires the address of the contract"))?;
+        let client = rpc_client(&conf.rpc)?;
+        let implementation = resolve_implementation(&client, address, ctx.block_number).await?;
+
+        debug!(%address, %implementation, block_number = ctx.block_number, "resolved proxy implementation");
+
+        let ctx = AbiContext { address: Some(implementation), block_number: ctx.block_number };
+        let inner = implementation_provider(&conf.source, &ctx, docs).await?;
+        Ok(Self { inner })
+    }
+}
+
+impl AbiItemProvider for ProxyAbiItemProvider {
+    fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
+        self.inner.get_abi_item(selector)
+    }
+
+    fn get_abi_items(&self, selector: &[u8]) -> Result<Vec<&AbiItem>> {
+        self.inner.get_abi_items(selector)
+    }
+
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        self.inner.get_anonymous_events()
+    }
+
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        self.inner.get_constructors()
+    }
+
+    fn is_strict(&self) -> bool {
+        self.inner.is_strict()
+    }
+
+    fn has_unmarked_events(&self) -> bool {
+        self.inner.has_unmarked_events()
+    }
+}
+
+fn rpc_client(url: &str) -> Result<Arc<RpcClient>> {
+    let mut clients = RPC_CLIENTS.lock().unwrap_or_else(|err| err.into_inner());
+
+    if let Some(client) = clients.get(url) {
+        return Ok(client.clone());
+    }
+
+    let client = Arc::new(RpcClient::new(url)?);
+    clients.insert(url.to_string(), client.clone());
+    Ok(client)
+}
+
+// Only the providers which could be created are kept, the others being created again for the next block.
+async fn implementation_provider(source: &str, ctx: &AbiContext, docs: &DocumentCache) -> Result<SharedAbiItemProvider> {
+    let key = (source.to_string(), AbiItemProviderFactory::context_scope(source).apply(ctx));
+
+    if let Some(p) = IMPLEMENTATIONS.get(&key) {
+        return Ok(p);
+    }
+
+    let p = AbiItemProviderFactory::create(source, &key.1, docs).await?;
+    IMPLEMENTATIONS.insert(key, p.clone());
+    Ok(p)
+}
+
+async fn resolve_implementation(client: &RpcClient, address: Address, block_number: Option<u64>) -> Result<Address> {
+    let block = block_number.map_or("latest".to_string(), |b| format!("0x{:x}", b));
+    let get_storage_at = |slot: B256| RpcCall {
+        method: "eth_getStorageAt".to_string(),
+        params: vec![json!(address), json!(slot), json!(block)]
+    };
+
+    let res = client.calls([
+        get_storage_at(EIP1967_IMPLEMENTATION_SLOT),
+        get_storage_at(EIP1967_BEACON_SLOT),
+        get_storage_at(EIP1822_PROXIABLE_SLOT),
+        RpcCall { method: "eth_getCode".to_string(), params: vec![json!(address), json!(block)] }
+    ]).await?;
+
+    let [implementation, beacon, proxiable, code] = <[RpcResult; 4]>::try_from(res)
+        .map_err(|_| anyhow!("unexpected number of RPC results"))?;
+
+    if let Some(implementation) = slot_address(implementation)? {
+        return Ok(implementation);
+    }
+
+    if let Some(beacon) = slot_address(beacon)? {
+        let res = client.calls([RpcCall {
+            method: "eth_call".to_string(),
+            params: vec![json!({"to": beacon, "data": hex::encode_prefixed(BEACON_IMPLEMENTATION_SELECTOR)}), json!(block)]
+        }]).await?;
+
+        return match res.into_iter().next() {
+            Some(res) => slot_address(res)?.ok_or(anyhow!("beacon {} has no implementation", beacon)),
+            None => bail!("no result for the implementation of beacon {}", beacon)
+        };
+    }
+
+    if let Some(implementation) = slot_address(proxiable)? {
+        return Ok(implementation);
+    }
+
+    match code {
+        RpcResult::Value(Value::String(code)) => Ok(eip1167_implementation(&hex::decode(code)?).unwrap_or(address)),
+        RpcResult::Value(v) => bail!("unexpected eth_getCode result: {}", v),
+        RpcResult::Error(err) => bail!(err)
+    }
+}
+
+// Implementation of an EIP-1167 minimal proxy, given its runtime code.
+fn eip1167_implementation(code: &[u8]) -> Option<Address> {
+    match code.strip_prefix(&EIP1167_PREFIX).and_then(|c| c.strip_suffix(&EIP1167_SUFFIX)) {
+        Some(implementation) if implementation.len() == 20 => Some(Address::from_slice(implementation)),
+        _ => None
+    }
+}
+
+// Address stored in a 32 bytes word, if not zero.
+fn slot_address(res: RpcResult) -> Result<Option<Address>> {
+    match res {
+        RpcResult::Value(Value::String(s)) => {
+            let word = hex::decode(s)?;
+            let word = word.get(word.len().saturating_sub(20)..).unwrap_or_default();
+            let address = Address::left_padding_from(word);
+            Ok(Some(address).filter(|a| !a.is_zero()))
+        },
+        RpcResult::Value(v) => bail!("unexpected RPC result: {}", v),
+        RpcResult::Error(err) => bail!(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use crate::evm::abi::file_abi_item_provider::FileAbiItemProvider;
+    use super::*;
+
+    const IMPLEMENTATION: Address = address!("bebc44782c7db0a1a60cb6fe97d0b483032ff1c7");
+
+    fn value(s: &str) -> RpcResult {
+        RpcResult::Value(json!(s))
+    }
+
+    #[test]
+    fn reads_slot_addresses() {
+        let word = "0x000000000000000000000000bebc44782c7db0a1a60cb6fe97d0b483032ff1c7";
+        assert_eq!(slot_address(value(word)).unwrap(), Some(IMPLEMENTATION));
+
+        // `eth_call` results and non-canonical words are read from their last 20 bytes
+        let dirty = "0xffffffffffffffffffffffffbebc44782c7db0a1a60cb6fe97d0b483032ff1c7";
+        assert_eq!(slot_address(value(dirty)).unwrap(), Some(IMPLEMENTATION));
+        assert_eq!(slot_address(value("0xbebc44782c7db0a1a60cb6fe97d0b483032ff1c7")).unwrap(), Some(IMPLEMENTATION));
+
+        assert_eq!(slot_address(value(&format!("0x{}", "00".repeat(32)))).unwrap(), None);
+        assert_eq!(slot_address(value("0x")).unwrap(), None);
+
+        assert!(slot_address(value("0xzz")).is_err());
+        assert!(slot_address(RpcResult::Value(Value::Null)).is_err());
+        assert!(slot_address(RpcResult::Error("execution reverted".to_string())).is_err());
+    }
+
+    #[test]
+    fn reads_eip1167_implementations() {
+        let code = hex!("363d3d373d3d3d363d73bebc44782c7db0a1a60cb6fe97d0b483032ff1c75af43d82803e903d91602b57fd5bf3");
+        assert_eq!(eip1167_implementation(&code), Some(IMPLEMENTATION));
+
+        // not a minimal proxy: truncated, with a different address length or any other code
+        assert_eq!(eip1167_implementation(&code[..code.len() - 1]), None);
+        let short = hex!("363d3d373d3d3d363d73bebc44782c7db0a1a60cb6fe97d0b483032ff15af43d82803e903d91602b57fd5bf3");
+        assert_eq!(eip1167_implementation(&short), None);
+        assert_eq!(eip1167_implementation(&hex!("6080604052")), None);
+        assert_eq!(eip1167_implementation(&[]), None);
+    }
+
+    #[test]
+    fn forwards_constructors() {
+        let inner = FileAbiItemProvider::from_inline(r#"["constructor(address owner)"]"#).unwrap();
+        let p = ProxyAbiItemProvider { inner: Arc::new(inner) };
+
+        assert_eq!(p.get_constructors().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn shares_rpc_clients_by_url() {
+        let a = rpc_client("http://127.0.0.1:8545").unwrap();
+        let b = rpc_client("http://127.0.0.1:8545").unwrap();
+        let c = rpc_client("http://127.0.0.1:8545#max-batch-size=10").unwrap();
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+use anyhow::{anyhow, Context, Result};
+use url::Url;
+use arrow::array::{AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::csv::ReaderBuilder as CsvReaderBuilder;
+use arrow::csv::reader::Format;
+use arrow::datatypes::DataType;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::Value;
+use tracing::debug;
+use super::abi_item::AbiItem;
+use super::abi_item_provider::AbiItemProvider;
+use super::object_store_url::parse_object_store_url;
+
+// Maps event and function selectors to candidate signatures read from a signature database,
+// e.g. an export of 4byte.directory. Several signatures may share a selector.
+pub struct SigdbAbiItemProvider {
+    m: HashMap<Vec<u8>, Vec<AbiItem>>
+}
+
+impl SigdbAbiItemProvider {
+    pub async fn new(s: &str) -> Result<Self> {
+        let u = Url::parse(s).context("cannot parse URL")?;
+        let file = Url::parse(&format!("file://{}", u.path())).context("cannot parse signature database path")?;
+        let (objstr, path) = parse_object_store_url(&file)?;
+        let content = objstr.get(&path).await?.bytes().await?;
+
+        let fullsigs = match path.extension() {
+            Some("parquet") => read_parquet(content)?,
+            Some("csv") => read_csv(&content)?,
+            Some("jsonl") | Some("ndjson") => read_jsonl(&content)?,
+            _ => read_lines(&content)?
+        };
+
+        let mut m: HashMap<Vec<u8>, Vec<AbiItem>> = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut invalid = 0;
+
+        for fullsig in fullsigs.iter().filter(|s| seen.insert(*s)) {
+            match parse_fullsig(fullsig) {
+                Ok(item) => m.entry(item.selector()).or_default().push(item),
+                Err(_) => invalid += 1
+            }
+        }
+
+        debug!(path = %path, selectors = m.len(), invalid, "loaded signature database");
+        Ok(Self { m })
+    }
+}
+
+impl AbiItemProvider for SigdbAbiItemProvider {
+    fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
+        self.get_abi_items(selector)?.into_iter().next().ok_or(anyhow!("no ABI field found for selector"))
+    }
+
+    fn get_abi_items(&self, selector: &[u8]) -> Result<Vec<&AbiItem>> {
+        let items = self.m.get(selector).ok_or(anyhow!("no ABI field found for selector"))?;
+        Ok(items.iter().collect())
+    }
+
+    fn is_strict(&self) -> bool {
+        true
+    }
+
+    fn has_unmarked_events(&self) -> bool {
+        true
+    }
+}
+
+// Signatures without a keyword, as found in most databases, are functions.
+fn parse_fullsig(s: &str) -> Result<AbiItem> {
+    match s.split_whitespace().next() {
+        Some("event") | Some("function") => AbiItem::parse(s),
+        _ => AbiItem::parse(&format!("function {}", s))
+    }
+}
+
+// The signatures are read from the `fullsig` column, or from the first column if there is none.
+fn fullsigs_from_batch(batch: &RecordBatch) -> Result<Vec<String>> {
+    let col = batch.column_by_name("fullsig")
+        .or(batch.columns().first())
+        .ok_or(anyhow!("signature database has no column"))?;
+    let col = cast(col, &DataType::Utf8)?;
+
+    Ok(col.as_string::<i32>().iter().flatten().map(str::to_string).collect())
+}
+
+fn read_parquet(content: impl parquet::file::reader::ChunkReader + 'static) -> Result<Vec<String>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(content)?.build()?;
+    let mut fullsigs = vec![];
+
+    for batch in reader {
+        fullsigs.extend(fullsigs_from_batch(&batch?)?);
+    }
+
+    Ok(fullsigs)
+}
+
+fn read_csv(content: &[u8]) -> Result<Vec<String>> {
+    let (schema, _) = Format::default()
+        .with_header(true)
+        .infer_schema(content, Some(100))?;
+    let reader = CsvReaderBuilder::new(schema.into())
+        .with_header(true)
+        .build(content)?;
+    let mut fullsigs = vec![];
+
+    for batch in reader {
+        fullsigs.extend(fullsigs_from_batch(&batch?)?);
+    }
+
+    Ok(fullsigs)
+}
+
+// Each line is either a JSON string or an object with a `fullsig` field.
+fn read_jsonl(content: &[u8]) -> Result<Vec<String>> {
+    std::str::from_utf8(content)?
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| match serde_json::from_str(l)? {
+            Value::String(s) => Ok(s),
+            Value::Object(mut o) => match o.remove("fullsig") {
+                Some(Value::String(s)) => Ok(s),
+                _ => Err(anyhow!("missing fullsig field"))
+            },
+            _ => Err(anyhow!("invalid signature database line"))
+        })
+        .collect()
+}
+
+fn read_lines(content: &[u8]) -> Result<Vec<String>> {
+    Ok(std::str::from_utf8(content)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+    use alloy::primitives::{B256, U256};
+    use crate::evm::abi::decoding::{decode_call, decode_event};
+    use super::*;
+
+    const TRANSFER_EVENT: [u8; 32] = hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+    const TRANSFER: [u8; 4] = hex!("a9059cbb");
+    const TRANSFER_FROM: [u8; 4] = hex!("23b872dd");
+
+    async fn load(file: &str) -> SigdbAbiItemProvider {
+        let path = format!("{}/tests/fixtures/sigdb/{file}", env!("CARGO_MANIFEST_DIR"));
+        SigdbAbiItemProvider::new(&format!("sigdb://{path}")).await.unwrap()
+    }
+
+    async fn check(file: &str) {
+        let p = load(file).await;
+        let fullsigs = |selector: &[u8]| -> Vec<String> {
+            p.get_abi_items(selector).unwrap().iter().map(|item| item.fullsig().to_string()).collect()
+        };
+
+        assert!(matches!(p.get_abi_item(&TRANSFER_EVENT), Ok(AbiItem::Event(_))));
+        assert_eq!(fullsigs(&TRANSFER).len(), 1, "duplicates are loaded once");
+        assert!(matches!(p.get_abi_item(&TRANSFER), Ok(AbiItem::Function(_))));
+        assert!(matches!(p.get_abi_item(&TRANSFER_FROM), Ok(AbiItem::Function(_))));
+        assert_eq!(p.m.len(), 3, "invalid signatures are skipped");
+        assert!(p.get_abi_item(&hex!("00000000")).is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_csv() {
+        check("sigs.csv").await;
+    }
+
+    #[tokio::test]
+    async fn reads_jsonl() {
+        check("sigs.jsonl").await;
+    }
+
+    #[tokio::test]
+    async fn reads_parquet() {
+        check("sigs.parquet").await;
+    }
+
+    #[tokio::test]
+    async fn reads_lines() {
+        check("sigs.txt").await;
+    }
+
+    #[tokio::test]
+    async fn decodes_colliding_functions() {
+        let p = load("collisions.txt").await;
+        let burn = hex!("42966c6800000000000000000000000000000000000000000000000000000000000003e8");
+        let collate = hex!("42966c6800112233445566778899aabbccddeeff00000000000000000000000000000000");
+
+        assert_eq!(p.get_functions(&burn[..4]).unwrap().len(), 2);
+
+        let decoded: Value = serde_json::from_slice(&decode_call(&p, &burn, None).unwrap()).unwrap();
+        assert_eq!(decoded["value"]["signature"], "burn(uint256)");
+        assert_eq!(decoded["value"]["inputs"]["arg0"], 1000);
+
+        let decoded: Value = serde_json::from_slice(&decode_call(&p, &collate, None).unwrap()).unwrap();
+        assert_eq!(decoded["value"]["signature"], "collate_propagate_storage(bytes16)");
+    }
+
+    #[tokio::test]
+    async fn decodes_events_without_indexed_markers() {
+        let p = load("collisions.txt").await;
+        let from = B256::left_padding_from(&hex!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"));
+        let to = B256::left_padding_from(&hex!("dac17f958d2ee523a2206206994597c13d831ec7"));
+        let value = B256::from(U256::from(1000));
+        let fullsig = |topics: &[B256], data: &[u8]| -> Value {
+            let decoded: Value = serde_json::from_slice(&decode_event(&p, topics, data).unwrap()).unwrap();
+            decoded["value"]["fullsig"].clone()
+        };
+
+        // ERC-20 and ERC-721 transfers
+        assert_eq!(fullsig(&[TRANSFER_EVENT.into(), from, to], value.as_slice()), "event Transfer(address indexed, address indexed, uint256)");
+        assert_eq!(fullsig(&[TRANSFER_EVENT.into(), from, to, value], &[]), "event Transfer(address indexed, address indexed, uint256 indexed)");
+        assert!(decode_event(&p, &[TRANSFER_EVENT.into(), from, to, value, value], &[]).is_err());
+    }
+
+    #[test]
+    fn parses_signatures_without_keyword_as_functions() {
+        assert!(matches!(parse_fullsig("transfer(address,uint256)"), Ok(AbiItem::Function(_))));
+        assert!(matches!(parse_fullsig("event Approval(address indexed,address indexed,uint256)"), Ok(AbiItem::Event(_))));
+    }
+}
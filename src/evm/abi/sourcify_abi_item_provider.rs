@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Context, Result};
+use url::Url;
+use serde::Deserialize;
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::Address;
+use object_store::ObjectStore;
+use object_store::path::Path;
+use super::abi_item::AbiItem;
+use super::event::Event;
+use super::constructor::Constructor;
+use super::abi_item_provider::AbiItemProvider;
+use super::object_store_url::parse_object_store_url;
+use super::file_abi_item_provider::FileAbiItemProvider;
+use super::document_cache::DocumentNotFound;
+
+const MATCH_KINDS: [&str; 2] = ["full_match", "partial_match"];
+
+#[derive(Debug, Deserialize)]
+struct SourcifyConfig {
+    chain: u64
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    output: MetadataOutput
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataOutput {
+    abi: JsonAbi
+}
+
+// Reads the verified ABI of a contract from a directory with the layout of the Sourcify repository,
+// i.e. `contracts/{full_match,partial_match}/<chain id>/<checksummed address>/metadata.json`.
+pub struct SourcifyAbiItemProvider {
+    inner: FileAbiItemProvider
+}
+
+impl SourcifyAbiItemProvider {
+    pub async fn new(s: &str, address: Option<Address>) -> Result<Self> {
+        let address = address.ok_or(anyhow!("sourcify decoder requires the address of the contract"))?;
+        let mut u = Url::parse(s).context("cannot parse URL")?;
+        let conf: SourcifyConfig = serde_qs::from_str(u.fragment().unwrap_or_default())
+            .context("cannot parse sourcify decoder options")?;
+
+        u.set_fragment(None);
+        let root = Url::parse(&format!("file://{}", u.path())).context("cannot parse sourcify repository path")?;
+        let (objstr, root) = parse_object_store_url(&root)?;
+
+        for match_kind in MATCH_KINDS {
+            let path = Path::from_iter(root.parts().chain([
+                "contracts".into(),
+                match_kind.into(),
+                conf.chain.to_string().as_str().into(),
+                address.to_checksum(None).as_str().into(),
+                "metadata.json".into()
+            ]));
+
+            match objstr.get(&path).await {
+                Ok(res) => {
+                    let content = res.bytes().await?;
+                    let metadata: Metadata = serde_json::from_slice(&content).context("cannot parse contract metadata")?;
+                    let inner = FileAbiItemProvider::from_json_abi(metadata.output.abi)?;
+                    return Ok(Self { inner });
+                },
+                Err(object_store::Error::NotFound { .. }) => continue,
+                Err(err) => return Err(err.into())
+            }
+        }
+
+        Err(DocumentNotFound(format!("no verified contract found for address {} on chain {}", address, conf.chain)).into())
+    }
+}
+
+impl AbiItemProvider for SourcifyAbiItemProvider {
+    fn get_abi_item(&self, selector: &[u8]) -> Result<&AbiItem> {
+        self.inner.get_abi_item(selector)
+    }
+
+    fn get_abi_items(&self, selector: &[u8]) -> Result<Vec<&AbiItem>> {
+        self.inner.get_abi_items(selector)
+    }
+
+    fn get_anonymous_events(&self) -> Vec<&Event> {
+        self.inner.get_anonymous_events()
+    }
+
+    fn get_constructors(&self) -> Result<Vec<&Constructor>> {
+        self.inner.get_constructors()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, hex};
+    use super::*;
+
+    const TOKEN: Address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+    const VAULT: Address = address!("bebc44782c7db0a1a60cb6fe97d0b483032ff1c7");
+    const TRANSFER: [u8; 32] = hex!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+    const APPROVAL: [u8; 32] = hex!("8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925");
+
+    // A repository with the full match of `TOKEN`, and partial matches of `TOKEN` and `VAULT`.
+    fn repository(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("ch_evm_sourcify_{}_{name}", std::process::id()));
+        let contracts = [
+            ("full_match", TOKEN, "event Transfer(address indexed from, address indexed to, uint256 value)"),
+            ("partial_match", TOKEN, "event Approval(address indexed owner, address indexed spender, uint256 value)"),
+            ("partial_match", VAULT, "event Approval(address indexed owner, address indexed spender, uint256 value)")
+        ];
+
+        for (match_kind, address, event) in contracts {
+            let dir = root.join(format!("contracts/{match_kind}/1/{}", address.to_checksum(None)));
+            let abi = serde_json::to_value(JsonAbi::parse([event]).unwrap()).unwrap();
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("metadata.json"), serde_json::json!({"output": {"abi": abi}}).to_string()).unwrap();
+        }
+
+        root
+    }
+
+    #[tokio::test]
+    async fn prefers_full_matches() {
+        let root = repository("full_match");
+        let p = SourcifyAbiItemProvider::new(&format!("sourcify://{}#chain=1", root.display()), Some(TOKEN)).await.unwrap();
+
+        assert!(p.get_abi_item(&TRANSFER).is_ok());
+        assert!(p.get_abi_item(&APPROVAL).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_partial_matches() {
+        let root = repository("partial_match");
+        let p = SourcifyAbiItemProvider::new(&format!("sourcify://{}#chain=1", root.display()), Some(VAULT)).await.unwrap();
+
+        assert!(p.get_abi_item(&APPROVAL).is_ok());
+        assert!(p.get_abi_item(&TRANSFER).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_unverified_contracts_as_not_found() {
+        let root = repository("not_found");
+        let url = format!("sourcify://{}#chain=1", root.display());
+
+        for (address, chain) in [(address!("1111111111111111111111111111111111111111"), 1), (TOKEN, 10)] {
+            let url = url.replace("chain=1", &format!("chain={chain}"));
+            let err = SourcifyAbiItemProvider::new(&url, Some(address)).await.err().unwrap();
+            assert!(err.downcast_ref::<DocumentNotFound>().is_some(), "{err}");
+        }
+
+        assert!(SourcifyAbiItemProvider::new(&url, None).await.is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
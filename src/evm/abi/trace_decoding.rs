@@ -0,0 +1,367 @@
+use anyhow::{anyhow, bail, Result};
+use alloy::hex;
+use alloy::primitives::{Address, B256};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tracing::debug;
+use super::abi_item_provider::AbiItemProvider;
+use super::creation_code::CreationCode;
+use super::decoding::{decode_call, decode_constructor, decode_event, decode_revert};
+
+// Maximum number of nested calls of a trace, each adding 2 levels to its JSON, the frame and its `calls`, plus 1
+// for the `Result` wrapping the root frame. Traces are parsed and decoded recursively, this keeps them well within
+// the stack of the worker threads.
+const MAX_CALL_DEPTH: usize = 256;
+const MAX_TRACE_DEPTH: usize = 2 * MAX_CALL_DEPTH + 1;
+
+// Parses the output of the `callTracer` of `debug_traceTransaction`, either the root call frame itself or the
+// result of `ethereum_rpc`, i.e. the frame wrapped in a `Result`.
+pub fn parse_trace(s: &[u8]) -> Result<Value> {
+    // The recursion limit of serde_json, 128 levels, would reject traces more than about 64 calls deep, hence the
+    // depth is checked beforehand instead.
+    if json_depth(s) > MAX_TRACE_DEPTH {
+        bail!("cannot parse trace: calls nested more than {} deep", MAX_CALL_DEPTH);
+    }
+
+    let mut de = serde_json::Deserializer::from_slice(s);
+    de.disable_recursion_limit();
+    let trace = Value::deserialize(&mut de)
+        .and_then(|trace| de.end().map(|_| trace))
+        .map_err(|err| anyhow!("cannot parse trace: {err:#}"))?;
+
+    match trace {
+        Value::Object(mut m) if !m.contains_key("type") => match (m.remove("value"), m.remove("error")) {
+            (Some(frame), _) => Ok(frame),
+            (None, Some(Value::String(err))) => bail!("trace error: {}", err),
+            (None, Some(err)) => bail!("trace error: {}", err),
+            (None, None) => bail!("trace is not a call frame")
+        },
+        Value::Object(m) => Ok(Value::Object(m)),
+        _ => bail!("trace is not a call frame")
+    }
+}
+
+// The addresses of the contracts called, created or emitting logs in a trace, i.e. the contracts whose ABI
+// can be needed to decode it.
+pub fn trace_addresses(frame: &Value) -> Vec<Address> {
+    let mut addresses = Vec::new();
+    collect_addresses(frame, &mut addresses);
+    addresses
+}
+
+fn collect_addresses(frame: &Value, addresses: &mut Vec<Address>) {
+    let logs = frame["logs"].as_array().into_iter().flatten();
+
+    for address in [address_field(&frame["to"])].into_iter().chain(logs.map(|log| address_field(&log["address"]))).flatten() {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    for call in frame["calls"].as_array().into_iter().flatten() {
+        collect_addresses(call, addresses);
+    }
+}
+
+// Decodes all the frames of a trace, with the providers given for the address of each frame or log, and
+// returns it JSON-encoded: each frame gets the `decoded` call, or constructor for creations, and the `revert`
+// error of its output if it reverted, and each log its `decoded` event. The frames are returned as a tree, as
+// in the trace, or flattened in execution order, each frame with its `traceAddress`, i.e. its path from the
+// root frame, and without its `calls`.
+pub fn decode_trace<'a, F>(frame: &Value, providers: F, flatten: bool) -> Result<Vec<u8>>
+where
+    F: Fn(Option<Address>) -> Vec<&'a dyn AbiItemProvider>
+{
+    let frame = decode_frame(frame, &providers)?;
+
+    let value = match flatten {
+        true => {
+            let mut frames = Vec::new();
+            flatten_frame(frame, &mut Vec::new(), &mut frames);
+            Value::Array(frames)
+        },
+        false => frame
+    };
+
+    Ok(serde_json::to_vec(&json!({"value": value}))?)
+}
+
+fn decode_frame<'a, F>(frame: &Value, providers: &F) -> Result<Value>
+where
+    F: Fn(Option<Address>) -> Vec<&'a dyn AbiItemProvider>
+{
+    let Value::Object(m) = frame else {
+        bail!("call frame is not an object");
+    };
+
+    let mut decoded_frame = m.clone();
+    let input = bytes_field(&frame["input"])?.unwrap_or_default();
+    let output = bytes_field(&frame["output"])?.filter(|output| !output.is_empty());
+    let reverted = m.contains_key("error");
+    let to = address_field(&frame["to"]);
+    let frame_providers = providers(to);
+
+    let decoded = match frame["type"].as_str().unwrap_or_default() {
+        "CREATE" | "CREATE2" => {
+            let code = match (&output, reverted) {
+                (Some(runtime), false) => CreationCode::RuntimeLength(runtime.len()),
+                _ => CreationCode::Unknown
+            };
+            frame_providers.iter().find_map(|p| {
+                decode_constructor(*p, &input, code)
+                    .inspect_err(|err| debug!(to = ?to, error = %err, "cannot decode constructor of trace frame"))
+                    .ok()
+            })
+        },
+        _ if input.len() >= 4 => {
+            let output = output.as_deref().filter(|_| !reverted);
+            frame_providers.iter().find_map(|p| {
+                decode_call(*p, &input, output)
+                    .or_else(|err| match output {
+                        // The output may not match the ABI, e.g. a function declared as returning a value
+                        // returning nothing, the call is decoded without it then.
+                        Some(_) => decode_call(*p, &input, None),
+                        None => Err(err)
+                    })
+                    .inspect_err(|err| debug!(to = ?to, error = %err, "cannot decode call of trace frame"))
+                    .ok()
+            })
+        },
+        _ => None
+    };
+
+    if let Some(decoded) = decoded {
+        decoded_frame.insert("decoded".to_string(), result_value(&decoded)?);
+    }
+
+    let revert = output.filter(|_| reverted).and_then(|output| {
+        decode_revert(&output).inspect_err(|err| debug!(to = ?to, error = %err, "cannot decode revert of trace frame")).ok()
+    });
+
+    if let Some(revert) = revert {
+        decoded_frame.insert("revert".to_string(), result_value(&revert)?);
+    }
+
+    if let Some(Value::Array(logs)) = m.get("logs") {
+        let logs = logs.iter().map(|log| decode_log(log, providers)).collect::<Result<Vec<_>>>()?;
+        decoded_frame.insert("logs".to_string(), Value::Array(logs));
+    }
+
+    if let Some(Value::Array(calls)) = m.get("calls") {
+        let calls = calls.iter().map(|call| decode_frame(call, providers)).collect::<Result<Vec<_>>>()?;
+        decoded_frame.insert("calls".to_string(), Value::Array(calls));
+    }
+
+    Ok(Value::Object(decoded_frame))
+}
+
+fn decode_log<'a, F>(log: &Value, providers: &F) -> Result<Value>
+where
+    F: Fn(Option<Address>) -> Vec<&'a dyn AbiItemProvider>
+{
+    let Value::Object(m) = log else {
+        bail!("log is not an object");
+    };
+
+    let mut decoded_log = m.clone();
+    let topics = log["topics"].as_array().into_iter().flatten()
+        .map(|topic| Ok(B256::try_from(bytes_field(topic)?.unwrap_or_default().as_slice())?))
+        .collect::<Result<Vec<B256>>>();
+    let data = bytes_field(&log["data"])?.unwrap_or_default();
+
+    match topics {
+        Ok(topics) => {
+            let address = address_field(&log["address"]);
+            let decoded = providers(address).iter().find_map(|p| {
+                decode_event(*p, &topics, &data)
+                    .inspect_err(|err| debug!(address = ?address, error = %err, "cannot decode log of trace frame"))
+                    .ok()
+            });
+
+            if let Some(decoded) = decoded {
+                decoded_log.insert("decoded".to_string(), result_value(&decoded)?);
+            }
+        },
+        Err(err) => debug!(error = %err, "invalid topics in trace log")
+    }
+
+    Ok(Value::Object(decoded_log))
+}
+
+fn flatten_frame(frame: Value, trace_address: &mut Vec<usize>, frames: &mut Vec<Value>) {
+    let Value::Object(mut m) = frame else {
+        return;
+    };
+
+    let calls = m.remove("calls");
+    m.insert("traceAddress".to_string(), json!(trace_address));
+    frames.push(Value::Object(m));
+
+    if let Some(Value::Array(calls)) = calls {
+        for (i, call) in calls.into_iter().enumerate() {
+            trace_address.push(i);
+            flatten_frame(call, trace_address, frames);
+            trace_address.pop();
+        }
+    }
+}
+
+// The `value` of a JSON-encoded `Result`.
+fn result_value(js: &[u8]) -> Result<Value> {
+    let mut res: Map<String, Value> = serde_json::from_slice(js)?;
+    res.remove("value").ok_or(anyhow!("result has no value"))
+}
+
+// Maximum nesting of the arrays and objects of a JSON document, which does not need to be valid.
+fn json_depth(s: &[u8]) -> usize {
+    let (mut depth, mut max_depth) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+
+    for &c in s {
+        match c {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            _ if in_string => (),
+            b'[' | b'{' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            },
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => ()
+        }
+    }
+
+    max_depth
+}
+
+fn bytes_field(v: &Value) -> Result<Option<Vec<u8>>> {
+    match v {
+        Value::String(s) => Ok(Some(hex::decode(s)?)),
+        Value::Null => Ok(None),
+        v => bail!("expected a hex string, got {}", v)
+    }
+}
+
+fn address_field(v: &Value) -> Option<Address> {
+    v.as_str().and_then(|s| s.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use super::super::file_abi_item_provider::FileAbiItemProvider;
+    use super::*;
+
+    const TOKEN: Address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+    const VAULT: Address = address!("bebc44782c7db0a1a60cb6fe97d0b483032ff1c7");
+    const USER: Address = address!("dac17f958d2ee523a2206206994597c13d831ec7");
+
+    // A deposit of 1000 tokens in a vault, checking the balance of the user, transferring the tokens, then trying
+    // to withdraw 2000 tokens, which reverts, and calling the user with an unknown selector.
+    const TRACE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/traces/vault_deposit.json"));
+
+    // Decodes the trace with the ABI of the token for the frames and logs of the token, and the one of the vault
+    // for the frames of the vault.
+    fn decode(flatten: bool) -> Value {
+        let token = FileAbiItemProvider::from_inline(r#"[
+            "function balanceOf(address owner) view returns (uint256)",
+            "function transfer(address to, uint256 value) returns (bool)",
+            "function transferFrom(address from, address to, uint256 value) returns (bool)",
+            "event Transfer(address indexed from, address indexed to, uint256 value)"
+        ]"#).unwrap();
+        let vault = FileAbiItemProvider::from_inline(r#"[
+            "function deposit(uint256 amount)",
+            "function withdraw(uint256 amount)"
+        ]"#).unwrap();
+        let providers = |address: Option<Address>| -> Vec<&dyn AbiItemProvider> {
+            match address {
+                Some(TOKEN) => vec![&token],
+                Some(VAULT) => vec![&vault],
+                _ => Vec::new()
+            }
+        };
+
+        let trace = parse_trace(TRACE).unwrap();
+        serde_json::from_slice(&decode_trace(&trace, providers, flatten).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn lists_trace_addresses() {
+        assert_eq!(trace_addresses(&parse_trace(TRACE).unwrap()), [VAULT, TOKEN, USER]);
+    }
+
+    #[test]
+    fn decodes_frames_and_logs() {
+        let root = &decode(false)["value"];
+        let calls = &root["calls"];
+
+        assert_eq!(root["decoded"]["signature"], "deposit(uint256)");
+        assert_eq!(root["gasUsed"], "0x20000");
+        assert_eq!(calls[0]["decoded"]["outputs"]["arg0"], 1000);
+
+        assert_eq!(calls[1]["decoded"]["signature"], "transferFrom(address,address,uint256)");
+        assert_eq!(calls[1]["decoded"]["outputs"]["arg0"], true);
+        assert_eq!(calls[1]["logs"][0]["decoded"]["signature"], "Transfer(address,address,uint256)");
+        assert_eq!(calls[1]["logs"][0]["decoded"]["inputs"]["arg1"], format!("{VAULT:#x}"));
+
+        // the reverted frame has no outputs, and the call it made returns nothing although declared returning a bool
+        assert_eq!(calls[2]["decoded"]["inputs"]["arg0"], 2000);
+        assert!(calls[2]["decoded"].get("outputs").is_none());
+        assert_eq!(calls[2]["revert"]["signature"], "Error(string)");
+        assert_eq!(calls[2]["revert"]["inputs"]["arg0"], "insufficient balance");
+        assert_eq!(calls[2]["calls"][0]["decoded"]["signature"], "transfer(address,uint256)");
+        assert!(calls[2]["calls"][0]["decoded"].get("outputs").is_none());
+
+        assert!(calls[3].get("decoded").is_none());
+        assert_eq!(calls[3]["input"], "0xdeadbeef");
+    }
+
+    #[test]
+    fn flattens_frames_in_execution_order() {
+        let frames = decode(true)["value"].as_array().unwrap().clone();
+        let trace_addresses: Vec<Value> = frames.iter().map(|frame| frame["traceAddress"].clone()).collect();
+
+        assert_eq!(trace_addresses, [json!([]), json!([0]), json!([1]), json!([2]), json!([2, 0]), json!([3])]);
+        assert!(frames.iter().all(|frame| frame.get("calls").is_none()));
+        assert_eq!(frames[4]["decoded"]["signature"], "transfer(address,uint256)");
+        assert_eq!(frames[2]["logs"][0]["decoded"]["signature"], "Transfer(address,address,uint256)");
+    }
+
+    // A trace of `depth` calls, each making the next one.
+    fn nested_trace(depth: usize) -> String {
+        let call = r#"{"type":"CALL","to":"0xbebc44782c7db0a1a60cb6fe97d0b483032ff1c7","input":"0x","calls":["#;
+        format!("{{\"value\":{}{}}}", call.repeat(depth), "]}".repeat(depth))
+    }
+
+    #[test]
+    fn parses_deep_traces() {
+        let trace = parse_trace(nested_trace(MAX_CALL_DEPTH).as_bytes()).unwrap();
+        assert_eq!(trace_addresses(&trace).len(), 1);
+
+        let decoded: Value = serde_json::from_slice(&decode_trace(&trace, |_| Vec::new(), true).unwrap()).unwrap();
+        let frames = decoded["value"].as_array().unwrap();
+        assert_eq!(frames.len(), MAX_CALL_DEPTH);
+        assert_eq!(frames[MAX_CALL_DEPTH - 1]["traceAddress"].as_array().unwrap().len(), MAX_CALL_DEPTH - 1);
+
+        assert!(decode_trace(&trace, |_| Vec::new(), false).is_ok());
+    }
+
+    #[test]
+    fn rejects_too_deep_traces() {
+        let err = parse_trace(nested_trace(MAX_CALL_DEPTH + 1).as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "cannot parse trace: calls nested more than 256 deep");
+
+        // not a trace, but still not parsed recursively
+        assert!(parse_trace("[".repeat(1_000_000).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let err = parse_trace(br#"{"type":"CALL","#).unwrap_err();
+        assert!(err.to_string().starts_with("cannot parse trace: EOF while parsing"), "{err}");
+
+        assert!(parse_trace(br#"{"type":"CALL"} {}"#).is_err());
+        assert_eq!(json_depth(br#"{"input":"[[{\"["}"#), 1);
+    }
+}
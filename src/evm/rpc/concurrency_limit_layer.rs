@@ -2,18 +2,21 @@
 
 use std::task::{Context,Poll};
 use std::sync::Arc;
+use std::time::Instant;
 use futures_core::ready;
 use tokio::sync::{Semaphore,OwnedSemaphorePermit};
 use tokio_util::sync::PollSemaphore;
 use alloy::transports::{TransportError,TransportFut};
 use alloy_json_rpc::{RequestPacket,ResponsePacket};
 use tower::{Layer,Service};
+use super::metrics::RPC_METRICS;
 
 #[derive(Debug)]
 pub struct ConcurrencyLimitService<S> {
     inner: S,
     sem: PollSemaphore,
     permit: Option<OwnedSemaphorePermit>,
+    wait_start: Option<Instant>,
 }
 
 impl<S> ConcurrencyLimitService<S> {
@@ -21,7 +24,8 @@ impl<S> ConcurrencyLimitService<S> {
         ConcurrencyLimitService{
             inner,
             sem: PollSemaphore::new(Arc::new(Semaphore::new(limit))),
-            permit: None
+            permit: None,
+            wait_start: None
         }
     }
 }
@@ -32,6 +36,7 @@ impl<T: Clone> Clone for ConcurrencyLimitService<T> {
             inner: self.inner.clone(),
             sem: self.sem.clone(),
             permit: None,
+            wait_start: None,
         }
     }
 }
@@ -49,7 +54,10 @@ where
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         if self.permit.is_none() {
+            let wait_start = *self.wait_start.get_or_insert_with(Instant::now);
             self.permit = ready!(self.sem.poll_acquire(cx));
+            self.wait_start = None;
+            RPC_METRICS.concurrency_wait.observe(wait_start.elapsed());
         }
 
         self.inner.poll_ready(cx)
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use alloy_json_rpc::{RequestPacket, ResponsePacket, ResponsePayload};
+use lazy_static::lazy_static;
+
+const LATENCY_BUCKETS: [f64; 13] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+lazy_static! {
+    pub static ref RPC_METRICS: RpcMetrics = RpcMetrics::default();
+}
+
+#[derive(Debug, Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64
+}
+
+impl Histogram {
+    pub fn observe(&self, d: Duration) {
+        let secs = d.as_secs_f64();
+
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|b| secs <= *b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(d.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+
+    // Upper bound of the bucket containing the given quantile, +Inf if it falls past the last bucket.
+    pub fn quantile(&self, q: f64) -> f64 {
+        let count = self.count();
+
+        if count == 0 {
+            return 0.0;
+        }
+
+        let rank = (q * count as f64).ceil() as u64;
+        let mut acc = 0;
+
+        for (i, b) in self.buckets.iter().enumerate() {
+            acc += b.load(Ordering::Relaxed);
+
+            if acc >= rank {
+                return LATENCY_BUCKETS[i];
+            }
+        }
+
+        f64::INFINITY
+    }
+
+    fn write_prometheus(&self, w: &mut String, name: &str, pid: u32) {
+        let _ = writeln!(w, "# TYPE {} histogram", name);
+        let mut acc = 0;
+
+        for (i, b) in self.buckets.iter().enumerate() {
+            acc += b.load(Ordering::Relaxed);
+            let _ = writeln!(w, "{}_bucket{{pid=\"{}\",le=\"{}\"}} {}", name, pid, LATENCY_BUCKETS[i], acc);
+        }
+
+        let _ = writeln!(w, "{}_bucket{{pid=\"{}\",le=\"+Inf\"}} {}", name, pid, self.count());
+        let _ = writeln!(w, "{}_sum{{pid=\"{}\"}} {}", name, pid, self.sum().as_secs_f64());
+        let _ = writeln!(w, "{}_count{{pid=\"{}\"}} {}", name, pid, self.count());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RpcMetrics {
+    pub calls: AtomicU64,
+    pub batches: AtomicU64,
+    pub requests: AtomicU64,
+    pub retries: AtomicU64,
+    pub errors: AtomicU64,
+    pub request_bytes: AtomicU64,
+    pub response_bytes: AtomicU64,
+    pub http_statuses: Mutex<BTreeMap<u16, u64>>,
+    pub call_latency: Histogram,
+    pub request_latency: Histogram,
+    pub concurrency_wait: Histogram
+}
+
+impl RpcMetrics {
+    pub fn inc(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn record_request(&self, req: &RequestPacket) {
+        let size: usize = req.requests().iter().map(|r| r.serialized().get().len()).sum();
+        Self::inc(&self.requests, 1);
+        Self::inc(&self.request_bytes, size as u64);
+    }
+
+    pub fn record_response(&self, resp: &ResponsePacket) {
+        let size: usize = resp.payloads()
+            .map(|p| match p {
+                ResponsePayload::Success(v) => v.get().len(),
+                ResponsePayload::Failure(e) => e.message.len() + e.data.as_ref().map(|d| d.get().len()).unwrap_or_default()
+            })
+            .sum();
+        Self::inc(&self.response_bytes, size as u64);
+    }
+
+    pub fn record_http_status(&self, status: u16) {
+        *self.http_statuses.lock().expect("poisoned lock").entry(status).or_default() += 1;
+    }
+
+    fn counters(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("calls", self.calls.load(Ordering::Relaxed)),
+            ("batches", self.batches.load(Ordering::Relaxed)),
+            ("requests", self.requests.load(Ordering::Relaxed)),
+            ("retries", self.retries.load(Ordering::Relaxed)),
+            ("errors", self.errors.load(Ordering::Relaxed)),
+            ("request_bytes", self.request_bytes.load(Ordering::Relaxed)),
+            ("response_bytes", self.response_bytes.load(Ordering::Relaxed)),
+        ]
+    }
+
+    fn histograms(&self) -> Vec<(&'static str, &Histogram)> {
+        vec![
+            ("call_latency", &self.call_latency),
+            ("request_latency", &self.request_latency),
+            ("concurrency_wait", &self.concurrency_wait),
+        ]
+    }
+
+    pub fn to_logfmt(&self) -> String {
+        let mut w = String::with_capacity(512);
+        let _ = write!(w, "msg=\"rpc stats\" pid={}", std::process::id());
+
+        for (name, v) in self.counters() {
+            let _ = write!(w, " {}={}", name, v);
+        }
+
+        for (status, v) in self.http_statuses.lock().expect("poisoned lock").iter() {
+            let _ = write!(w, " http_status_{}={}", status, v);
+        }
+
+        for (name, h) in self.histograms() {
+            let _ = write!(w, " {}_count={} {}_p50={} {}_p99={}",
+                name, h.count(),
+                name, h.quantile(0.5),
+                name, h.quantile(0.99)
+            );
+        }
+
+        w
+    }
+
+    pub fn to_prometheus(&self) -> String {
+        let mut w = String::with_capacity(4096);
+        let pid = std::process::id();
+
+        for (name, v) in self.counters() {
+            let _ = writeln!(w, "# TYPE ch_evm_rpc_{}_total counter", name);
+            let _ = writeln!(w, "ch_evm_rpc_{}_total{{pid=\"{}\"}} {}", name, pid, v);
+        }
+
+        let _ = writeln!(w, "# TYPE ch_evm_rpc_http_status_total counter");
+
+        for (status, v) in self.http_statuses.lock().expect("poisoned lock").iter() {
+            let _ = writeln!(w, "ch_evm_rpc_http_status_total{{pid=\"{}\",status=\"{}\"}} {}", pid, status, v);
+        }
+
+        for (name, h) in self.histograms() {
+            h.write_prometheus(&mut w, &format!("ch_evm_rpc_{}_seconds", name), pid);
+        }
+
+        w
+    }
+    // Writes to a temporary file first so node_exporter never reads a partial file.
+    pub fn write_prometheus_textfile(&self, path: &str) -> std::io::Result<()> {
+        let path = path.replace("{pid}", &std::process::id().to_string());
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, self.to_prometheus())?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(latencies_ms: &[u64]) -> Histogram {
+        let h = Histogram::default();
+        for ms in latencies_ms {
+            h.observe(Duration::from_millis(*ms));
+        }
+        h
+    }
+
+    #[test]
+    fn computes_quantiles() {
+        assert_eq!(histogram(&[]).quantile(0.5), 0.0);
+
+        let h = histogram(&[1, 2, 3, 20, 40, 200, 200, 800, 4000, 90_000]);
+        assert_eq!(h.count(), 10);
+        assert_eq!(h.sum(), Duration::from_millis(95_266));
+        assert_eq!(h.quantile(0.1), 0.005);
+        assert_eq!(h.quantile(0.3), 0.005);
+        assert_eq!(h.quantile(0.5), 0.05);
+        assert_eq!(h.quantile(0.7), 0.25);
+        assert_eq!(h.quantile(0.9), 5.0);
+        assert_eq!(h.quantile(0.99), f64::INFINITY);
+    }
+
+    fn metrics() -> RpcMetrics {
+        let m = RpcMetrics::default();
+        RpcMetrics::inc(&m.calls, 3);
+        RpcMetrics::inc(&m.batches, 1);
+        RpcMetrics::inc(&m.errors, 2);
+        m.record_http_status(429);
+        m.record_http_status(429);
+        m.record_http_status(503);
+        m.call_latency.observe(Duration::from_millis(30));
+        m.call_latency.observe(Duration::from_millis(70));
+        m
+    }
+
+    #[test]
+    fn renders_logfmt() {
+        let expected = format!(
+            "msg=\"rpc stats\" pid={} calls=3 batches=1 requests=0 retries=0 errors=2 request_bytes=0 response_bytes=0 \
+            http_status_429=2 http_status_503=1 \
+            call_latency_count=2 call_latency_p50=0.05 call_latency_p99=0.1 \
+            request_latency_count=0 request_latency_p50=0 request_latency_p99=0 \
+            concurrency_wait_count=0 concurrency_wait_p50=0 concurrency_wait_p99=0",
+            std::process::id()
+        );
+
+        assert_eq!(metrics().to_logfmt(), expected);
+    }
+
+    #[test]
+    fn renders_prometheus() {
+        let pid = std::process::id();
+        let s = metrics().to_prometheus();
+        let lines: Vec<&str> = s.lines().collect();
+
+        for line in [
+            "# TYPE ch_evm_rpc_calls_total counter".to_string(),
+            format!("ch_evm_rpc_calls_total{{pid=\"{pid}\"}} 3"),
+            format!("ch_evm_rpc_http_status_total{{pid=\"{pid}\",status=\"429\"}} 2"),
+            format!("ch_evm_rpc_http_status_total{{pid=\"{pid}\",status=\"503\"}} 1"),
+            "# TYPE ch_evm_rpc_call_latency_seconds histogram".to_string(),
+            format!("ch_evm_rpc_call_latency_seconds_bucket{{pid=\"{pid}\",le=\"0.025\"}} 0"),
+            format!("ch_evm_rpc_call_latency_seconds_bucket{{pid=\"{pid}\",le=\"0.05\"}} 1"),
+            format!("ch_evm_rpc_call_latency_seconds_bucket{{pid=\"{pid}\",le=\"0.1\"}} 2"),
+            format!("ch_evm_rpc_call_latency_seconds_bucket{{pid=\"{pid}\",le=\"60\"}} 2"),
+            format!("ch_evm_rpc_call_latency_seconds_bucket{{pid=\"{pid}\",le=\"+Inf\"}} 2"),
+            format!("ch_evm_rpc_call_latency_seconds_sum{{pid=\"{pid}\"}} 0.1"),
+            format!("ch_evm_rpc_call_latency_seconds_count{{pid=\"{pid}\"}} 2")
+        ] {
+            assert!(lines.contains(&line.as_str()), "missing {line} in\n{s}");
+        }
+
+        // every sample follows the TYPE line of its metric
+        let types = lines.iter().filter(|l| l.starts_with("# TYPE")).count();
+        assert_eq!(types, 7 + 1 + 3);
+        assert!(lines.iter().all(|l| l.starts_with('#') || l.split(' ').count() == 2), "{s}");
+    }
+
+    #[test]
+    fn writes_prometheus_textfiles() {
+        let dir = std::env::temp_dir().join(format!("ch_evm_metrics_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rpc_{pid}.prom");
+
+        metrics().write_prometheus_textfile(path.to_str().unwrap()).unwrap();
+
+        let written = dir.join(format!("rpc_{}.prom", std::process::id()));
+        assert_eq!(std::fs::read_to_string(&written).unwrap(), metrics().to_prometheus());
+        assert!(!dir.join(format!("rpc_{}.prom.tmp", std::process::id())).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
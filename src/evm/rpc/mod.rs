@@ -1,5 +1,7 @@
 mod rpc_client;
 mod retry_layer;
 mod concurrency_limit_layer;
+mod metrics;
 
-pub use rpc_client::{RpcClient,RpcCall,RpcResult,BatchResult};
\ No newline at end of file
+pub use rpc_client::{RpcClient,RpcCall,RpcResult,BatchResult};
+pub use metrics::{RpcMetrics,RPC_METRICS};
\ No newline at end of file
@@ -1,5 +1,5 @@
 use std::task::{Context,Poll};
-use std::time::Duration;
+use std::time::{Duration,Instant};
 use backoff::backoff::Backoff;
 use backoff::exponential::{ExponentialBackoff, ExponentialBackoffBuilder};
 use backoff::SystemClock;
@@ -8,6 +8,7 @@ use alloy::transports::{TransportError,TransportErrorKind,TransportFut};
 use alloy_json_rpc::{RequestPacket,ResponsePacket,ErrorPayload};
 use tower::{Layer,Service};
 use tokio::time::sleep;
+use super::metrics::{RpcMetrics,RPC_METRICS};
 
 #[derive(Debug, Clone)]
 pub struct RetryConfig {
@@ -58,13 +59,24 @@ where
 
             loop {
                 tries += 1;
+                RPC_METRICS.record_request(&request);
+                let start = Instant::now();
                 let res = inner.call(request.clone()).await;
+                RPC_METRICS.request_latency.observe(start.elapsed());
 
-                if res.is_ok() {
-                    return res
-                }
+                let err = match res {
+                    Ok(resp) => {
+                        RPC_METRICS.record_response(&resp);
+                        return Ok(resp)
+                    },
+                    Err(err) => err
+                };
+
+                RpcMetrics::inc(&RPC_METRICS.errors, 1);
 
-                let err = res.unwrap_err();
+                if let TransportError::Transport(TransportErrorKind::HttpError(e)) = &err {
+                    RPC_METRICS.record_http_status(e.status);
+                }
 
                 if !this.should_retry(&err) {
                     return Err(err);
@@ -80,6 +92,7 @@ where
                     )));
                 }
 
+                RpcMetrics::inc(&RPC_METRICS.retries, 1);
                 sleep(delay.unwrap()).await;
             }
         })
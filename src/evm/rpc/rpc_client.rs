@@ -1,7 +1,7 @@
 use core::str;
 use std::clone::Clone;
 use std::fmt::Debug;
-use std::time::Duration;
+use std::time::{Duration,Instant};
 use anyhow::{Context,Result,bail};
 use futures::future::join_all;
 use itertools::Itertools;
@@ -14,6 +14,7 @@ use alloy::transports::http::reqwest::Url;
 use duration_str::deserialize_duration;
 use super::retry_layer::{RetryLayer,RetryConfig};
 use super::concurrency_limit_layer::ConcurrencyLimitLayer;
+use super::metrics::{RpcMetrics,RPC_METRICS};
 
 pub struct RpcCall {
     pub method: String,
@@ -121,6 +122,7 @@ impl RpcClient {
     where
         I: IntoIterator<Item = RpcCall> + Send,
     {      
+        let start = Instant::now();
         let mut call_futs = Vec::new();
 
         let batch_call_futs = calls
@@ -138,28 +140,41 @@ impl RpcClient {
             })
             .collect::<Result<Vec<_>>>()?;
 
+        RpcMetrics::inc(&RPC_METRICS.batches, batch_call_futs.len() as u64);
+        RpcMetrics::inc(&RPC_METRICS.calls, call_futs.len() as u64);
+
         join_all(batch_call_futs).await
             .into_iter()
             .try_for_each(|x| x.context("HTTP call error"))?;
         
-        join_all(call_futs).await
+        let res = join_all(call_futs).await
             .into_iter()
             .map(|res| self.process_rpc_result(res))
-            .collect::<BatchResult>()
+            .collect::<BatchResult>();
+
+        RPC_METRICS.call_latency.observe(start.elapsed());
+        res
     }
 
     pub async fn multi_call<I>(&self, calls: I) -> BatchResult
     where
         I: IntoIterator<Item = RpcCall> + Send,
     {
+        let start = Instant::now();
         let call_futs = calls
             .into_iter()
-            .map(|call| self.client.request(call.method, call.params));
+            .map(|call| self.client.request(call.method, call.params))
+            .collect::<Vec<_>>();
+
+        RpcMetrics::inc(&RPC_METRICS.calls, call_futs.len() as u64);
+
+        let res = join_all(call_futs).await
+            .into_iter()
+            .map(|res| self.process_rpc_result(res))
+            .collect::<BatchResult>();
 
-            join_all(call_futs).await
-                .into_iter()
-                .map(|res| self.process_rpc_result(res))
-                .collect::<BatchResult>()
+        RPC_METRICS.call_latency.observe(start.elapsed());
+        res
     }
 
     fn process_rpc_result(&self, res: Result<Value, RpcError<TransportErrorKind>>) -> Result<RpcResult>{